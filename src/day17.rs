@@ -20,19 +20,8 @@ struct Robot(Direction);
 
 #[inline(always)]
 fn solve(input: &str) -> impl Display {
-    let mut machine = Machine::from_csv(input);
-
-    let mut camera_view = String::new();
-
-    let mut stepper = run_until_block(None);
-
-    loop {
-        match stepper(&mut machine) {
-            MachineState::NeedInput => break,
-            MachineState::Halt => break,
-            MachineState::Output(byte) => camera_view.push((byte as u8) as char),
-        }
-    }
+    let machine = Machine::from_csv(input);
+    let camera_view = AsciiMachine::new(machine).read_all().text;
 
     let rows = camera_view.trim_matches('\n').lines().map(|line| {
         line.trim_matches('\n').as_bytes().iter().map(|&c| match c {
@@ -0,0 +1,325 @@
+#![allow(unused_imports)]
+
+// SOLUTION CODE GOES HERE
+
+// Remove if this is not an intcode problem
+mod intcode;
+use intcode::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Empty,
+    Scaffold,
+    Rb(Robot),
+}
+
+use Cell::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Robot(Direction);
+
+/// Set to true to watch the robot's continuous video feed on stderr
+const VIDEO_FEED: bool = false;
+const FRAME_DELAY: Duration = Duration::from_millis(30);
+
+/// The robot's memory for each routine, in characters (excluding the newline)
+const ROUTINE_LIMIT: usize = 20;
+
+/// The number of movement functions (A, B and C)
+const FUNCTION_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NavInstruction {
+    Left,
+    Right,
+    Straight(usize),
+}
+
+impl Display for NavInstruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NavInstruction::Left => 'L'.fmt(f),
+            NavInstruction::Right => 'R'.fmt(f),
+            NavInstruction::Straight(n) => n.fmt(f),
+        }
+    }
+}
+
+/// The length of a routine when it's sent to the robot, as comma-separated
+/// items
+fn routine_len<T: Display>(items: &[T]) -> usize {
+    let commas = items.len().saturating_sub(1);
+    items
+        .iter()
+        .map(|item| item.to_string().len())
+        .sum::<usize>()
+        + commas
+}
+
+/// Parse the camera view into a grid, and find the robot
+fn parse_camera(camera_view: &str) -> (SparseGrid<Cell>, Location, Direction) {
+    let rows = camera_view.trim_matches('\n').lines().map(|line| {
+        line.trim_matches('\n').as_bytes().iter().map(|&c| match c {
+            b'#' => Scaffold,
+            b'.' => Empty,
+            b'^' => Rb(Robot(Up)),
+            b'v' => Rb(Robot(Down)),
+            b'<' => Rb(Robot(Left)),
+            b'>' => Rb(Robot(Right)),
+            _ => panic!("Invalid cell: {}", c),
+        })
+    });
+
+    let mut robot = None;
+    let mut grid = SparseGrid::new_default((0, 0), Cell::Empty);
+
+    for (r, row) in rows.enumerate() {
+        for (c, cell) in row.enumerate() {
+            let location = Row(r as isize) + Column(c as isize);
+            grid.insert(location, cell);
+
+            if let Rb(Robot(direction)) = cell {
+                robot = Some((location, direction));
+            }
+        }
+    }
+
+    let (location, direction) = robot.expect("Robot not found in camera view");
+    (grid, location, direction)
+}
+
+/// Follow the scaffold from the robot's position to the end, going straight
+/// through intersections, and turning only when there's no scaffold ahead.
+fn trace_path(
+    grid: &SparseGrid<Cell>,
+    mut location: Location,
+    mut direction: Direction,
+) -> Vec<NavInstruction> {
+    let is_scaffold = |location| match grid.get(location) {
+        Ok(Scaffold) | Ok(Rb(..)) => true,
+        _ => false,
+    };
+
+    let mut path = Vec::new();
+
+    loop {
+        let mut steps = 0;
+        while is_scaffold(location + direction) {
+            location = location + direction;
+            steps += 1;
+        }
+
+        if steps > 0 {
+            path.push(NavInstruction::Straight(steps));
+        }
+
+        if is_scaffold(location + direction.anticlockwise()) {
+            direction = direction.anticlockwise();
+            path.push(NavInstruction::Left);
+        } else if is_scaffold(location + direction.clockwise()) {
+            direction = direction.clockwise();
+            path.push(NavInstruction::Right);
+        } else {
+            break path;
+        }
+    }
+}
+
+/// Split the path into a main routine of calls to up to 3 functions, each
+/// of which fits in the robot's memory. Tries the longest functions first.
+fn compress<'a>(
+    path: &'a [NavInstruction],
+    functions: &mut Vec<&'a [NavInstruction]>,
+    main: &mut Vec<usize>,
+) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+
+    // Each call takes 2 characters, with its comma
+    if (main.len() + 1) * 2 - 1 > ROUTINE_LIMIT {
+        return false;
+    }
+
+    for id in 0..functions.len() {
+        let function = functions[id];
+
+        if path.starts_with(function) {
+            main.push(id);
+            if compress(&path[function.len()..], functions, main) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+
+    if functions.len() < FUNCTION_COUNT {
+        let longest = (1..=path.len())
+            .take_while(|&len| routine_len(&path[..len]) <= ROUTINE_LIMIT)
+            .last()
+            .unwrap_or(0);
+
+        for len in (1..=longest).rev() {
+            functions.push(&path[..len]);
+            main.push(functions.len() - 1);
+
+            if compress(&path[len..], functions, main) {
+                return true;
+            }
+
+            main.pop();
+            functions.pop();
+        }
+    }
+
+    false
+}
+
+/// Send a routine to the robot, after waiting for its prompt
+fn send_routine(robot: &mut AsciiMachine, prompt: &str, routine: &str) {
+    let transcript = robot.read_until_prompt(prompt);
+    if transcript.stop != ReadStop::Prompt {
+        panic!("Expected prompt {:?}, got:\n{}", prompt, transcript.text);
+    }
+
+    robot.send_line(routine);
+}
+
+#[inline(always)]
+fn solve(input: &str) -> impl Display {
+    let machine = Machine::from_csv(input);
+
+    let camera_view = AsciiMachine::new(machine.clone()).read_all().text;
+    let (grid, location, direction) = parse_camera(&camera_view);
+
+    let path = trace_path(&grid, location, direction);
+
+    let mut functions = Vec::new();
+    let mut main = Vec::new();
+
+    if !compress(&path, &mut functions, &mut main) {
+        panic!("Couldn't fit the path into {} functions", FUNCTION_COUNT);
+    }
+
+    // Wake the robot up
+    let mut machine = machine;
+    set(2, address(0))(&mut machine);
+    let mut robot = AsciiMachine::new(machine);
+
+    let main = main.iter().map(|&id| (b'A' + id as u8) as char);
+    send_routine(&mut robot, "Main:\n", &main.join_with(',').to_string());
+
+    for (id, name) in ["A", "B", "C"].iter().enumerate() {
+        let routine = functions
+            .get(id)
+            .map(|function| function.iter().join_with(',').to_string())
+            // Unused functions still have to be sent
+            .unwrap_or_else(|| "L".to_string());
+
+        let prompt = format!("Function {}:\n", name);
+        send_routine(&mut robot, &prompt, &routine);
+    }
+
+    send_routine(
+        &mut robot,
+        "Continuous video feed?\n",
+        if VIDEO_FEED { "y" } else { "n" },
+    );
+
+    // The video feed is a series of camera views, separated by blank lines,
+    // followed by the amount of dust collected
+    let mut frame = String::new();
+    let mut dust = None;
+
+    for line in robot.lines() {
+        match line {
+            AsciiLine::Value(value) => dust = Some(value),
+            AsciiLine::Text(ref text) if text.is_empty() => {
+                if VIDEO_FEED && !frame.is_empty() {
+                    eprintln!("{}", frame);
+                    sleep(FRAME_DELAY);
+                }
+                frame.clear();
+            }
+            AsciiLine::Text(text) => {
+                frame.push_str(&text);
+                frame.push('\n');
+            }
+        }
+    }
+
+    dust.expect("The robot didn't report any dust")
+}
+
+/*
+ * SUPPORTING LIBRARY CODE GOES HERE:
+ *
+ * - Imports & use statements for tons of common traits, data structures, etc
+ * - `fn main` bootstrap that reads from stdin and writes the solution to stdout
+ * - Utility traits
+ * - Anything else that might be broadly useful for other problems
+ */
+
+#[global_allocator]
+static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write as FmtWrite};
+use std::hash::Hash;
+use std::io::{self, Read, Write as IoWrite};
+use std::iter::{self, FromIterator, FusedIterator, Peekable};
+use std::mem::{replace, swap};
+use std::ops::Add;
+use std::rc::{Rc, Weak};
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// String joins
+use joinery::prelude::*;
+
+// Grids
+use gridly::prelude::*;
+use gridly_grids::*;
+
+// Generation-based simulations
+use generations::*;
+
+// Formatting things without creating intermediary strings
+use lazy_format::lazy_format;
+
+// Cascading init
+use cascade::cascade;
+
+// Integer traits
+use num::Integer;
+
+// Parsing
+use nom::{
+    bytes::complete::tag,
+    character::complete::{alpha1, digit1, multispace0, multispace1, space0, space1},
+    combinator::{all_consuming, iterator, map, map_res, opt, recognize},
+    multi::{many0, separated_list},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    IResult,
+};
+
+#[inline(always)]
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    let end = Instant::now();
+    (result, end - start)
+}
+
+fn main() {
+    let mut input = String::with_capacity(4096);
+    io::stdin()
+        .read_to_string(&mut input)
+        .unwrap_or_else(|err| panic!("Error reading input from stdin: {}", err));
+    let (solution, duration) = timed(move || solve(&input));
+    println!("{}", solution);
+    eprintln!("Algorithm duration: {:?}", duration);
+}
@@ -11,11 +11,15 @@ fn solve(input: &str) -> impl Display {
     let init = Machine::from_csv(input);
     let mut machine = Machine::default();
 
+    // The program is run from scratch for every location, so keep its
+    // decoded instructions around between runs.
+    let mut cache = DecodeCache::new();
+
     // Check if a location is covered by the tractor beam.
     let mut check_location = move |location: Location| {
         machine.clone_from(&init);
 
-        match run_until_block([location.column.0, location.row.0].iter().copied())(&mut machine) {
+        match cache.run_until_block([location.column.0, location.row.0].iter().copied())(&mut machine) {
             MachineState::Output(0) => false,
             MachineState::Output(1) => true,
             state => panic!("Unexpected state {:?} at {:?}", state, location),
@@ -6,6 +6,424 @@
 mod intcode;
 use intcode::*;
 
+/// The springdroid can only remember this many instructions
+const MAX_INSTRUCTIONS: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DroidMode {
+    /// Sensors A through D
+    Walk,
+
+    /// Sensors A through I
+    Run,
+}
+
+impl DroidMode {
+    fn sensor_count(self) -> usize {
+        match self {
+            DroidMode::Walk => 4,
+            DroidMode::Run => 9,
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            DroidMode::Walk => "WALK",
+            DroidMode::Run => "RUN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    /// A sensor, as a distance from the droid (A is 0)
+    Sensor(usize),
+    Temp,
+    Jump,
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Register::Sensor(n) => ((b'A' + n as u8) as char).fmt(f),
+            Register::Temp => 'T'.fmt(f),
+            Register::Jump => 'J'.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpringOp {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SpringInstruction {
+    op: SpringOp,
+    source: Register,
+    dest: Register,
+}
+
+impl Display for SpringInstruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let op = match self.op {
+            SpringOp::And => "AND",
+            SpringOp::Or => "OR",
+            SpringOp::Not => "NOT",
+        };
+
+        write!(f, "{} {} {}", op, self.source, self.dest)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Springscript {
+    instructions: Vec<SpringInstruction>,
+    mode: DroidMode,
+}
+
+impl Springscript {
+    /// Run the script against the sensor readings (true for ground), and
+    /// return true if the droid should jump.
+    fn jumps(&self, sensors: &[bool]) -> bool {
+        let mut temp = false;
+        let mut jump = false;
+
+        for instruction in &self.instructions {
+            let source = match instruction.source {
+                Register::Sensor(n) => sensors[n],
+                Register::Temp => temp,
+                Register::Jump => jump,
+            };
+
+            let dest = match instruction.dest {
+                Register::Temp => &mut temp,
+                Register::Jump => &mut jump,
+                Register::Sensor(..) => unreachable!("Scripts can't write to sensors"),
+            };
+
+            *dest = match instruction.op {
+                SpringOp::And => source && *dest,
+                SpringOp::Or => source || *dest,
+                SpringOp::Not => !source,
+            };
+        }
+
+        jump
+    }
+}
+
+impl Display for Springscript {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+
+        writeln!(f, "{}", self.mode.command())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScriptError {
+    line: usize,
+    message: String,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl FromStr for Springscript {
+    type Err = ScriptError;
+
+    /// Parse a script, one instruction per line, ending with WALK or RUN.
+    /// Checks that the registers are valid for the mode, and that the script
+    /// fits in the droid's memory.
+    fn from_str(input: &str) -> Result<Self, ScriptError> {
+        let mut instructions = Vec::new();
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let error = |line, message: String| ScriptError { line, message };
+
+        // Parse the registers as we go, but check them against the mode at
+        // the end, since the mode comes last
+        let (mode_line, mode) = loop {
+            let (line, text) = lines
+                .next()
+                .ok_or_else(|| error(input.lines().count(), "Missing WALK or RUN".to_string()))?;
+
+            let mut words = text.split_whitespace();
+
+            let op = match words.next() {
+                Some("WALK") => break (line, DroidMode::Walk),
+                Some("RUN") => break (line, DroidMode::Run),
+                Some("AND") => SpringOp::And,
+                Some("OR") => SpringOp::Or,
+                Some("NOT") => SpringOp::Not,
+                Some(word) => return Err(error(line, format!("Unknown instruction {:?}", word))),
+                None => unreachable!(),
+            };
+
+            let mut register = || match words.next() {
+                Some("T") => Ok(Register::Temp),
+                Some("J") => Ok(Register::Jump),
+                Some(word) if word.len() == 1 && ("A"..="I").contains(&word) => {
+                    Ok(Register::Sensor((word.as_bytes()[0] - b'A') as usize))
+                }
+                Some(word) => Err(error(line, format!("Unknown register {:?}", word))),
+                None => Err(error(line, "Missing register".to_string())),
+            };
+
+            let source = register()?;
+            let dest = register()?;
+
+            if let Register::Sensor(..) = dest {
+                return Err(error(line, format!("Can't write to sensor {}", dest)));
+            }
+
+            if let Some(word) = words.next() {
+                return Err(error(line, format!("Unexpected {:?}", word)));
+            }
+
+            instructions.push((line, SpringInstruction { op, source, dest }));
+        };
+
+        if let Some((line, _)) = lines.next() {
+            return Err(error(
+                line,
+                format!("Unexpected line after {}", mode.command()),
+            ));
+        }
+
+        if instructions.len() > MAX_INSTRUCTIONS {
+            return Err(error(
+                mode_line,
+                format!(
+                    "Too many instructions: {} (the limit is {})",
+                    instructions.len(),
+                    MAX_INSTRUCTIONS
+                ),
+            ));
+        }
+
+        for (line, instruction) in &instructions {
+            if let Register::Sensor(n) = instruction.source {
+                if n >= mode.sensor_count() {
+                    return Err(error(
+                        *line,
+                        format!(
+                            "Sensor {} isn't available in {} mode",
+                            instruction.source,
+                            mode.command()
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(Springscript {
+            instructions: instructions
+                .into_iter()
+                .map(|(_, instruction)| instruction)
+                .collect(),
+            mode,
+        })
+    }
+}
+
+/// A section of hull, as reported by the droid when it falls. The droid
+/// starts on the first tile; everything past the end is ground.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Hull {
+    ground: Vec<bool>,
+}
+
+impl Hull {
+    fn is_ground(&self, position: usize) -> bool {
+        self.ground.get(position).cloned().unwrap_or(true)
+    }
+
+    /// Walk a droid running the script across the hull. Returns the
+    /// position of the hole it fell into, if it didn't make it across.
+    fn simulate(&self, script: &Springscript) -> Result<(), usize> {
+        let mut position = 0;
+        let mut sensors = [false; 9];
+
+        while position < self.ground.len() {
+            if !self.is_ground(position) {
+                return Err(position);
+            }
+
+            for (distance, sensor) in sensors.iter_mut().enumerate() {
+                *sensor = self.is_ground(position + distance + 1);
+            }
+
+            // A jump lands 4 tiles ahead
+            position += if script.jumps(&sensors) { 4 } else { 1 };
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Hull {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for &ground in &self.ground {
+            f.write_str(if ground { "#" } else { "." })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of running a script on the real springdroid
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    /// The droid made it across, and reported the hull damage
+    Damage(isize),
+
+    /// The droid fell into a hole in this hull
+    Fell(Hull),
+}
+
+/// Find the hull in the droid's report of its fall. The first frame shows
+/// the droid (@) standing above the hull.
+fn parse_fall(report: &str) -> Option<Hull> {
+    let mut lines = report.lines();
+    let droid = lines.by_ref().find_map(|line| line.find('@'))?;
+    let hull = lines.next()?;
+
+    Some(Hull {
+        ground: hull
+            .as_bytes()
+            .get(droid..)?
+            .iter()
+            .map(|&c| c == b'#')
+            .collect(),
+    })
+}
+
+fn run_droid(program: &Machine, script: &Springscript) -> Verdict {
+    let mut droid = AsciiMachine::new(program.clone());
+
+    for line in script.to_string().lines() {
+        droid.send_line(line);
+    }
+
+    let transcript = droid.read_all();
+
+    if transcript.stop != ReadStop::Halt {
+        panic!("Droid didn't halt after the script:\n{}", transcript.text);
+    }
+
+    match transcript.values.last() {
+        Some(&damage) => Verdict::Damage(damage),
+        None => Verdict::Fell(parse_fall(&transcript.text).unwrap_or_else(|| {
+            panic!(
+                "Couldn't find the hull in the droid's report:\n{}",
+                transcript.text
+            )
+        })),
+    }
+}
+
+/// Candidate scripts for a mode, cheapest first. Each script computes
+///
+///   J = (!n1 || !n2 || ...) && p1 && p2 && ... && (q1 || q2 || ...)
+///
+/// for some disjoint sets of sensors N, P and Q, with N nonempty and Q
+/// either empty or of at least 2 sensors: "jump if there's a hole ahead,
+/// as long as it's safe to land and keep going".
+fn candidates(mode: DroidMode) -> Vec<Springscript> {
+    use Register::*;
+    use SpringOp::*;
+
+    let sensors = mode.sensor_count();
+    let mut scripts = Vec::new();
+
+    // Assign each sensor to none, N, P or Q, as base-4 digits
+    for assignment in 0..4usize.pow(sensors as u32) {
+        let group = |n: usize| (assignment / 4usize.pow(n as u32)) % 4;
+        let members = |g| (0..sensors).filter(move |&n| group(n) == g).map(Sensor);
+
+        let negated: Vec<Register> = members(1).collect();
+        let positive: Vec<Register> = members(2).collect();
+        let either: Vec<Register> = members(3).collect();
+
+        if negated.is_empty() || either.len() == 1 {
+            continue;
+        }
+
+        let mut instructions = Vec::new();
+        let mut push = |op, source, dest| instructions.push(SpringInstruction { op, source, dest });
+
+        push(Not, negated[0], Jump);
+        for &sensor in &negated[1..] {
+            push(Not, sensor, Temp);
+            push(Or, Temp, Jump);
+        }
+
+        for &sensor in &positive {
+            push(And, sensor, Jump);
+        }
+
+        if let Some((&first, rest)) = either.split_first() {
+            push(Not, first, Temp);
+            push(Not, Temp, Temp);
+            for &sensor in rest {
+                push(Or, sensor, Temp);
+            }
+            push(And, Temp, Jump);
+        }
+
+        if instructions.len() <= MAX_INSTRUCTIONS {
+            scripts.push(Springscript { instructions, mode });
+        }
+    }
+
+    scripts.sort_by_key(|script| script.instructions.len());
+    scripts
+}
+
+/// Search for a script that gets the droid across. Each candidate is first
+/// checked against every hull the droid has fallen into so far; if it
+/// survives them all, it's tried on the real droid, and if the droid falls
+/// again, the new hull is added to the list. Returns the script, the hull
+/// damage, and the hulls found along the way.
+fn search(program: &Machine, mode: DroidMode) -> Option<(Springscript, isize, Vec<Hull>)> {
+    let mut hulls: Vec<Hull> = Vec::new();
+
+    for script in candidates(mode) {
+        if hulls.iter().any(|hull| hull.simulate(&script).is_err()) {
+            continue;
+        }
+
+        match run_droid(program, &script) {
+            Verdict::Damage(damage) => return Some((script, damage, hulls)),
+            Verdict::Fell(hull) => {
+                if hull.simulate(&script).is_ok() {
+                    panic!(
+                        "The droid fell on hull {}, but the simulation made it across with:\n{}",
+                        hull, script
+                    );
+                }
+
+                hulls.push(hull);
+            }
+        }
+    }
+
+    None
+}
+
 fn main() {
     let stdin = io::stdin();
     let mut stdin_lock = stdin.lock();
@@ -14,30 +432,46 @@ fn main() {
     stdin_lock
         .read_line(&mut input)
         .unwrap_or_else(|err| panic!("Error reading input from stdin: {}", err));
-    let mut machine = Machine::from_csv(&input);
-
-    let input_bytes = stdin_lock.bytes().map(|b| match b {
-        Ok(b) => b as isize,
-        Err(err) => panic!("Error reading input from stdin: {}", err),
-    });
+    let program = Machine::from_csv(&input);
 
-    let mut stepper = run_until_block(input_bytes);
+    let mut script = String::new();
+    stdin_lock
+        .read_to_string(&mut script)
+        .unwrap_or_else(|err| panic!("Error reading input from stdin: {}", err));
 
     let stdout = io::stdout();
     let stdout_lock = stdout.lock();
     let mut buffered = LineWriter::new(stdout_lock);
 
-    loop {
-        match stepper(&mut machine) {
-            MachineState::Halt => break,
-            MachineState::NeedInput => panic!("Unexpected unfullfilled input request"),
-            MachineState::Output(output) => {
-                if output > 127 {
-                    writeln!(buffered, "damage: {}", output).unwrap();
-                } else {
-                    let byte = output as u8;
-                    buffered.write_all(&[byte]).unwrap();
+    // With a script on stdin, check it and run it. Otherwise, search for
+    // scripts for both parts.
+    if !script.trim().is_empty() {
+        let script: Springscript = script
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid springscript: {}", err));
+
+        match run_droid(&program, &script) {
+            Verdict::Damage(damage) => writeln!(buffered, "damage: {}", damage).unwrap(),
+            Verdict::Fell(hull) => {
+                writeln!(buffered, "fell: {}", hull).unwrap();
+                if let Err(position) = hull.simulate(&script) {
+                    writeln!(buffered, "simulated fall at {}", position).unwrap();
+                }
+            }
+        }
+
+        return;
+    }
+
+    for &mode in &[DroidMode::Walk, DroidMode::Run] {
+        match search(&program, mode) {
+            None => writeln!(buffered, "No {} script found", mode.command()).unwrap(),
+            Some((script, damage, hulls)) => {
+                writeln!(buffered, "{}", script).unwrap();
+                for hull in hulls {
+                    writeln!(buffered, "avoided: {}", hull).unwrap();
                 }
+                writeln!(buffered, "damage: {}", damage).unwrap();
             }
         }
     }
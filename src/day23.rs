@@ -6,79 +6,27 @@
 mod intcode;
 use intcode::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Packet {
-    dest: usize,
-    value: isize,
-}
-
 #[inline(always)]
 fn solve(input: &str) -> impl Display {
     let init = Machine::from_csv(input);
 
-    let mut machines: Vec<Machine> = iter::repeat_with(|| init.clone()).take(50).collect();
+    let mut network = Network::new(&init, 50);
+    network.set_nat(LastPacketNat::new());
 
-    let mut nat_packet = None;
     let mut last_y = None;
 
-    // We need to initialize the machines with their addresses
-    let mut queue: VecDeque<Packet> = (0..50usize)
-        .map(|addr| Packet {
-            dest: addr,
-            value: addr as isize,
-        })
-        .collect();
-
-    loop {
-        // Two phase iteration cycle: deliver packets, then (when the queue is
-        // totally empty) send -1 to each machine.
-        while let Some(packet) = queue.pop_front() {
-            // eprintln!("machine {} receives {}", packet.dest, packet.value);
-
-            let machine = &mut machines[packet.dest];
-            let mut stepper = run_until_block(Some(packet.value));
-
-            loop {
-                match stepper(machine) {
-                    MachineState::Halt => panic!("Unexpected halt!"),
-                    MachineState::NeedInput => break,
-                    MachineState::Output(dest) => {
-                        let dest = dest as usize;
-                        // We're getting a packet. The first value is the
-                        // address, then we get X and Y (which we deliver as
-                        // two separate packets)
-                        let x = stepper(machine).expect_out("reading packet x");
-                        let y = stepper(machine).expect_out("reading packet y");
-
-                        if dest == 255 {
-                            nat_packet = Some((x, y));
-                        } else {
-                            queue.push_back(Packet { dest, value: x });
-                            queue.push_back(Packet { dest, value: y });
-                        }
-                    }
-                }
+    // Find the first Y value sent by the NAT twice in a row
+    network
+        .find_map(|event| match event {
+            NetworkEvent::NatSent(packet) if Some(packet.y) == last_y => Some(packet.y),
+            NetworkEvent::NatSent(packet) => {
+                last_y = Some(packet.y);
+                None
             }
-        }
-
-        // At this point, all machines are blocked on input. Insert the NAT
-        // packet, or send the no input signals to everyone
-        match nat_packet.take() {
-            Some((x, y)) => {
-                if Some(y) == last_y {
-                    return y;
-                } else {
-                    last_y = Some(y);
-                }
-
-                queue.push_back(Packet { dest: 0, value: x });
-                queue.push_back(Packet { dest: 0, value: y });
-            }
-            None => {
-                queue.extend((0..50usize).map(|dest| Packet { dest, value: -1 }));
-            }
-        }
-    }
+            NetworkEvent::Halted(address) => panic!("Unexpected halt at {}!", address),
+            _ => None,
+        })
+        .expect("Network shut down")
 }
 
 /*
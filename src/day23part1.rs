@@ -6,66 +6,18 @@
 mod intcode;
 use intcode::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Packet {
-    dest: usize,
-    value: isize,
-}
-
 #[inline(always)]
 fn solve(input: &str) -> impl Display {
     let init = Machine::from_csv(input);
 
-    let mut machines: Vec<Machine> = iter::repeat_with(|| init.clone()).take(50).collect();
-
-    // We need to initialize the machines with their addresses
-    let mut queue: VecDeque<Packet> = (0..50usize)
-        .map(|addr| Packet {
-            dest: addr,
-            value: addr as isize,
+    // There's no NAT, so packets to 255 are unrouted
+    Network::new(&init, 50)
+        .find_map(|event| match event {
+            NetworkEvent::Unrouted(packet) if packet.dest == 255 => Some(packet.y),
+            NetworkEvent::Halted(address) => panic!("Unexpected halt at {}!", address),
+            _ => None,
         })
-        .collect();
-
-    loop {
-        // Two phase iteration cycle: deliver packets, then (when the queue is
-        // totally empty) send -1 to each machine.
-        while let Some(packet) = queue.pop_front() {
-            // eprintln!("machine {} receives {}", packet.dest, packet.value);
-
-            let machine = &mut machines[packet.dest];
-            let mut stepper = run_until_block(Some(packet.value));
-
-            loop {
-                match stepper(machine) {
-                    MachineState::Halt => panic!("Unexpected halt!"),
-                    MachineState::NeedInput => break,
-                    MachineState::Output(dest) => {
-                        let dest = dest as usize;
-                        // We're getting a packet. The first value is the
-                        // address, then we get X and Y (which we deliver as
-                        // two separate packets)
-                        let x = stepper(machine).expect_out("reading packet x");
-                        let y = stepper(machine).expect_out("reading packet y");
-
-                        if dest == 255 {
-                            return y;
-                        }
-
-                        queue.reserve(2);
-                        queue.push_back(Packet { dest, value: x });
-                        queue.push_back(Packet { dest, value: y });
-                        // eprintln!("machine {} sends ({}, {}) to {}", packet.dest, x, y, dest);
-                    }
-                }
-            }
-        }
-
-        // At this point, all machines are blocked on input
-        queue.extend((0..50usize).map(|addr| Packet {
-            dest: addr,
-            value: -1,
-        }));
-    }
+        .expect("Network shut down")
 }
 
 /*
@@ -11,49 +11,32 @@ use intcode::*;
 #[inline(always)]
 fn solve(input: &str) -> impl Display {
     let init = Machine::from_csv(input);
-    let mut machines: [Machine; 5] = [
-        Machine::new_empty(),
-        Machine::new_empty(),
-        Machine::new_empty(),
-        Machine::new_empty(),
-        Machine::new_empty(),
-    ];
     let mut phases = [5, 6, 7, 8, 9];
     let mut best = 0;
 
     heap_recursive(&mut phases, |phases| {
+        let mut scheduler = Scheduler::new(Policy::RoundRobin);
 
-        machines[..]
-            .iter_mut()
-            .zip(phases.iter())
-            .for_each(|(machine, &phase)| {
-                // reset the machine
-                machine.clone_from(&init);
-
-                // feed the phase into the machine
-                match feed(phase)(machine) {
-                    MachineState::NeedInput => {},
-                    MachineState::Output(..) => panic!("Unexpected output; only gave phase so far"),
-                    MachineState::Halt => panic!("Unexpected halt"),
-                }
-
-            });
-
-        let mut final_signal = 0;
-        let mut signal = 0;
-
-        'outer: loop {
-            for machine in &mut machines {
-                match feed(signal)(machine) {
-                    MachineState::NeedInput => panic!("Unexpected request for input"),
-                    MachineState::Output(s) => signal = s,
-                    MachineState::Halt => break 'outer,
-                }
-            }
-            final_signal = signal;
+        // Each amplifier gets its phase, then the output of the previous
+        // amplifier; the last one feeds back into the first.
+        for &phase in phases.iter() {
+            let id = scheduler.add(init.clone());
+            scheduler.push_input(id, Some(phase));
         }
 
-        best = final_signal.max(best);
+        for id in 0..5 {
+            scheduler.connect(id, (id + 1) % 5);
+        }
+
+        scheduler.push_input(0, Some(0));
+
+        let report = scheduler.run();
+        if !report.all_halted() {
+            panic!("Amplifiers didn't all halt:\n{}", report);
+        }
+
+        let signal = scheduler.last_output(4).expect("No output from the last amplifier");
+        best = signal.max(best);
     });
 
     best
@@ -0,0 +1,342 @@
+#![allow(unused_imports)]
+
+// An interactive intcode debugger. Run with the path to a program:
+//
+//     ./start.sh debugger && cargo run --release -- program.txt
+//
+// Type `help` at the prompt for a list of commands.
+
+mod intcode;
+use intcode::*;
+
+const HELP: &str = "\
+Commands:
+  step [n]             (s) Run n instructions (default 1)
+  continue             (c) Run until a breakpoint, watchpoint, input wait, or halt
+  break <addr>         (b) Set a breakpoint
+  delete <addr>        (d) Remove a breakpoint
+  watch mem <addr>     Break when a memory cell changes
+  watch rb <value>     Break when the relative base crosses a value
+  watch out <value>    Break when a value is output
+  unwatch ...          Remove a watchpoint (same arguments as watch)
+  list                 Show breakpoints and watchpoints
+  regs                 (r) Print the IP, relative base, and pending input
+  dis [addr] [count]   Disassemble count instructions (default: at IP, 10)
+  dump <addr> [count]  Print count memory cells (default 10)
+  poke <addr> <value>  Write a value to memory (below address 16777216)
+  input <values...>    (i) Queue input values
+  ascii <text>         (a) Queue ASCII text, followed by a newline
+  mode num|ascii       Print output as numbers or ASCII text
+  save <file>          Save the machine and pending input to a snapshot file
+  load <file>          Restore a snapshot file (breakpoints are kept)
+  help                 (h) Show this message
+  quit                 (q) Exit";
+
+/// The largest address that can be poked. Memory is dense, so writing to a
+/// huge address would try to allocate all of the cells below it.
+const MAX_POKE_ADDRESS: usize = (1 << 24) - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    Numeric,
+    Ascii,
+}
+
+struct Session {
+    debugger: Debugger,
+    output_mode: OutputMode,
+}
+
+impl Session {
+    fn print_output(&self, value: isize) {
+        match self.output_mode {
+            OutputMode::Ascii if (0..128).contains(&value) => print!("{}", value as u8 as char),
+            _ => println!("output: {}", value),
+        }
+    }
+
+    fn print_stop(&self, state: DebugState) {
+        match state {
+            DebugState::Output(value) => self.print_output(value),
+            DebugState::NeedInput => println!("waiting for input"),
+            DebugState::Halt => println!("halted"),
+            DebugState::Break { reason, output } => {
+                if let Some(value) = output {
+                    self.print_output(value);
+                }
+                println!("break: {}", reason);
+            }
+        }
+    }
+
+    fn print_current(&self) {
+        let ip = self.debugger.ip();
+        if let Some((address, instruction)) = self
+            .debugger
+            .machine()
+            .disassemble_from(ip)
+            .instructions
+            .first()
+        {
+            println!("{:>5}: {}", address, instruction);
+        }
+    }
+
+    fn step(&mut self, count: usize) -> Result<(), IntcodeError> {
+        for _ in 0..count {
+            match self.debugger.step()? {
+                None => {}
+                Some(DebugState::Output(value)) => self.print_output(value),
+                Some(state) => {
+                    self.print_stop(state);
+                    break;
+                }
+            }
+        }
+        self.print_current();
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.debugger.run()? {
+                DebugState::Output(value) => self.print_output(value),
+                state => {
+                    if self.output_mode == OutputMode::Ascii {
+                        println!();
+                    }
+                    self.print_stop(state);
+                    break;
+                }
+            }
+        }
+        self.print_current();
+        Ok(())
+    }
+
+    fn print_registers(&self) {
+        println!("ip: {}", self.debugger.ip());
+        println!("rb: {}", self.debugger.relative_base());
+        println!(
+            "pending input: {}",
+            self.debugger.pending_input().iter().join_with(", ")
+        );
+        self.print_current();
+    }
+
+    fn list(&self) {
+        println!(
+            "breakpoints: {}",
+            self.debugger.breakpoints().iter().join_with(", ")
+        );
+        for watchpoint in self.debugger.watchpoints() {
+            println!("watch: {:?}", watchpoint);
+        }
+    }
+
+    fn disassemble(&self, start: usize, count: usize) {
+        let ip = self.debugger.ip();
+        let listing = self.debugger.machine().disassemble_from(start);
+
+        for (address, instruction) in listing.instructions.iter().take(count) {
+            let marker = if *address == ip { "=>" } else { "  " };
+            println!("{} {:>5}: {}", marker, address, instruction);
+        }
+    }
+
+    fn dump(&self, start: usize, count: usize) {
+        let machine = self.debugger.machine();
+        for cell in start..start.saturating_add(count) {
+            println!("{:>5}: {}", cell, machine.get(address(cell)));
+        }
+    }
+
+    fn poke(&mut self, cell: usize, value: isize) -> Result<(), String> {
+        if cell > MAX_POKE_ADDRESS {
+            return Err(format!(
+                "Can't poke address {}; the largest is {}",
+                cell, MAX_POKE_ADDRESS
+            ));
+        }
+
+        set(value, address(cell))(self.debugger.machine_mut());
+        Ok(())
+    }
+
+    fn save(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|err| format!("Error creating {}: {}", path, err))?;
+        self.debugger
+            .save_to(BufWriter::new(file))
+            .map_err(|err| format!("Error saving {}: {}", path, err))
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let file = File::open(path).map_err(|err| format!("Error opening {}: {}", path, err))?;
+        let loaded = Debugger::load_from(BufReader::new(file))
+            .map_err(|err| format!("Error loading {}: {}", path, err))?;
+
+        // Keep the existing breakpoints and watchpoints
+        let old = mem::replace(&mut self.debugger, loaded);
+        for &address in old.breakpoints() {
+            self.debugger.add_breakpoint(address);
+        }
+        for &watchpoint in old.watchpoints() {
+            self.debugger.add_watchpoint(watchpoint);
+        }
+
+        self.print_current();
+        Ok(())
+    }
+
+    /// Run a single command line. Returns false if the session should end.
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            None => return Ok(true),
+            Some(command) => command,
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (command, args.as_slice()) {
+            ("q", []) | ("quit", []) => return Ok(false),
+            ("h", []) | ("help", []) => println!("{}", HELP),
+            ("s", args) | ("step", args) => {
+                let count = parse_or(args.first(), 1)?;
+                self.step(count).map_err(|err| err.to_string())?;
+            }
+            ("c", []) | ("continue", []) => self.run().map_err(|err| err.to_string())?,
+            ("b", [addr]) | ("break", [addr]) => {
+                self.debugger.add_breakpoint(parse(addr)?);
+            }
+            ("d", [addr]) | ("delete", [addr]) => {
+                if !self.debugger.remove_breakpoint(parse(addr)?) {
+                    println!("no breakpoint at {}", addr);
+                }
+            }
+            ("watch", [kind, value]) => self.debugger.add_watchpoint(watchpoint(kind, value)?),
+            ("unwatch", [kind, value]) => {
+                if !self.debugger.remove_watchpoint(watchpoint(kind, value)?) {
+                    println!("no such watchpoint");
+                }
+            }
+            ("list", []) => self.list(),
+            ("r", []) | ("regs", []) => self.print_registers(),
+            ("dis", args) => {
+                let start = parse_or(args.first(), self.debugger.ip())?;
+                let count = parse_or(args.get(1), 10)?;
+                self.disassemble(start, count);
+            }
+            ("dump", args) if !args.is_empty() => {
+                let count = parse_or(args.get(1), 10)?;
+                self.dump(parse(args[0])?, count);
+            }
+            ("poke", [addr, value]) => self.poke(parse(addr)?, parse(value)?)?,
+            ("i", values) | ("input", values) => {
+                let values = values
+                    .iter()
+                    .map(|value| parse(value))
+                    .collect::<Result<Vec<isize>, String>>()?;
+                self.debugger.push_input(values);
+            }
+            ("a", _) | ("ascii", _) => {
+                let text = line.trim_start()[command.len()..].trim_start();
+                self.debugger
+                    .push_input(text.bytes().chain(iter::once(b'\n')).map(isize::from));
+            }
+            ("save", [path]) => self.save(path)?,
+            ("load", [path]) => self.load(path)?,
+            ("mode", ["num"]) => self.output_mode = OutputMode::Numeric,
+            ("mode", ["ascii"]) => self.output_mode = OutputMode::Ascii,
+            _ => return Err(format!("Unrecognized command: {}", line.trim())),
+        }
+
+        Ok(true)
+    }
+}
+
+fn parse<T: FromStr>(word: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    word.parse()
+        .map_err(|err| format!("Invalid number {:?}: {}", word, err))
+}
+
+fn parse_or<T: FromStr>(word: Option<&&str>, default: T) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    match word {
+        None => Ok(default),
+        Some(word) => parse(word),
+    }
+}
+
+fn watchpoint(kind: &str, value: &str) -> Result<Watchpoint, String> {
+    match kind {
+        "mem" => Ok(Watchpoint::Memory(parse(value)?)),
+        "rb" => Ok(Watchpoint::RelativeBase(parse(value)?)),
+        "out" => Ok(Watchpoint::Output(parse(value)?)),
+        _ => Err(format!("Unknown watchpoint kind: {}", kind)),
+    }
+}
+
+/*
+ * SUPPORTING LIBRARY CODE GOES HERE:
+ *
+ * - Imports & use statements for tons of common traits, data structures, etc
+ * - `fn main` bootstrap that loads the program and runs the command loop
+ */
+
+#[global_allocator]
+static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write as IoWrite};
+use std::iter;
+use std::mem;
+use std::process;
+use std::str::FromStr;
+
+// String joins
+use joinery::prelude::*;
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: debugger <program.txt>");
+        process::exit(2);
+    });
+
+    let machine = Machine::load_csv(&path).unwrap_or_else(|err| {
+        eprintln!("Failed to load {}: {}", path, err);
+        process::exit(1);
+    });
+
+    let mut session = Session {
+        debugger: Debugger::new(machine),
+        output_mode: OutputMode::Numeric,
+    };
+
+    session.print_current();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("(intcode) ");
+        io::stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            None => break,
+            Some(line) => line.unwrap_or_else(|err| panic!("Error reading stdin: {}", err)),
+        };
+
+        match session.command(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => println!("{}", err),
+        }
+    }
+}
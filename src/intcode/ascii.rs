@@ -0,0 +1,168 @@
+//! An adapter for programs which communicate in ASCII text, such as the
+//! camera in day 17 or the springdroid in day 21. Input is sent a line at a
+//! time, and output is read back as text. Output values which aren't ASCII
+//! (outside 0 to 127) are reported separately as `Value`s, since they usually
+//! carry the puzzle answer.
+
+use std::collections::VecDeque;
+use std::iter;
+use std::mem;
+
+use super::{run_until_block, Machine, MachineState, Memory};
+
+/// A single output of an ASCII machine, or the reason it stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsciiEvent {
+    Char(char),
+
+    /// An output value that isn't an ASCII character
+    Value(isize),
+
+    /// The machine needs input, and there are no more queued lines
+    NeedInput,
+    Halt,
+}
+
+/// A line of output from an ASCII machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiLine {
+    /// A line of text, without its newline
+    Text(String),
+
+    /// An output value that isn't an ASCII character
+    Value(isize),
+}
+
+/// Why a read stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadStop {
+    /// The text ended with the prompt being waited for
+    Prompt,
+    NeedInput,
+    Halt,
+}
+
+/// Everything output by a machine during a read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub text: String,
+
+    /// Output values which weren't ASCII characters, in order
+    pub values: Vec<isize>,
+    pub stop: ReadStop,
+}
+
+/// A machine which is sent lines of text and outputs text. See the module
+/// documentation for details.
+#[derive(Debug, Clone)]
+pub struct AsciiMachine<M = Vec<isize>> {
+    machine: Machine<M>,
+    input: VecDeque<isize>,
+}
+
+impl<M: Memory<Word = isize>> AsciiMachine<M> {
+    pub fn new(machine: Machine<M>) -> Self {
+        AsciiMachine {
+            machine,
+            input: VecDeque::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<M> {
+        &self.machine
+    }
+
+    /// Get the machine back, along with any queued input it hasn't read
+    pub fn into_inner(self) -> (Machine<M>, VecDeque<isize>) {
+        (self.machine, self.input)
+    }
+
+    /// Queue a line of input, followed by a newline. The machine reads it as
+    /// it runs. Panics if the line contains non-ASCII characters.
+    pub fn send_line(&mut self, line: &str) {
+        assert!(line.is_ascii(), "Non-ASCII input line: {:?}", line);
+
+        self.input.extend(
+            line.bytes()
+                .chain(iter::once(b'\n'))
+                .map(|byte| byte as isize),
+        );
+    }
+
+    /// The input that has been queued but not yet read by the machine
+    pub fn pending_input(&self) -> &VecDeque<isize> {
+        &self.input
+    }
+
+    /// Run the machine until its next output, or until it blocks or halts
+    pub fn next_event(&mut self) -> AsciiEvent {
+        let input = &mut self.input;
+
+        match run_until_block(iter::from_fn(move || input.pop_front()))(&mut self.machine) {
+            MachineState::Output(value) if (0..=127).contains(&value) => {
+                AsciiEvent::Char(value as u8 as char)
+            }
+            MachineState::Output(value) => AsciiEvent::Value(value),
+            MachineState::NeedInput => AsciiEvent::NeedInput,
+            MachineState::Halt => AsciiEvent::Halt,
+        }
+    }
+
+    /// Read output until the text ends with `prompt`, or until the machine
+    /// blocks or halts.
+    pub fn read_until_prompt(&mut self, prompt: &str) -> Transcript {
+        self.read(Some(prompt))
+    }
+
+    /// Read all output until the machine blocks on input or halts
+    pub fn read_all(&mut self) -> Transcript {
+        self.read(None)
+    }
+
+    fn read(&mut self, prompt: Option<&str>) -> Transcript {
+        let mut text = String::new();
+        let mut values = Vec::new();
+
+        let stop = loop {
+            match self.next_event() {
+                AsciiEvent::Char(c) => {
+                    text.push(c);
+                    match prompt {
+                        Some(prompt) if text.ends_with(prompt) => break ReadStop::Prompt,
+                        _ => {}
+                    }
+                }
+                AsciiEvent::Value(value) => values.push(value),
+                AsciiEvent::NeedInput => break ReadStop::NeedInput,
+                AsciiEvent::Halt => break ReadStop::Halt,
+            }
+        };
+
+        Transcript { text, values, stop }
+    }
+
+    /// Iterate over the lines of output until the machine blocks or halts.
+    /// Non-ASCII values are yielded as they're output, between lines. A
+    /// final line without a newline is yielded when the machine stops.
+    pub fn lines(&mut self) -> impl Iterator<Item = AsciiLine> + '_ {
+        let mut line = String::new();
+        let mut done = false;
+
+        iter::from_fn(move || {
+            while !done {
+                match self.next_event() {
+                    AsciiEvent::Char('\n') => return Some(AsciiLine::Text(mem::take(&mut line))),
+                    AsciiEvent::Char(c) => line.push(c),
+                    AsciiEvent::Value(value) => return Some(AsciiLine::Value(value)),
+                    AsciiEvent::NeedInput | AsciiEvent::Halt => done = true,
+                }
+            }
+
+            if line.is_empty() {
+                None
+            } else {
+                Some(AsciiLine::Text(mem::take(&mut line)))
+            }
+        })
+    }
+}
@@ -0,0 +1,389 @@
+//! An assembler for intcode programs. The syntax matches the disassembler's
+//! listing format, so a listing can be assembled back into the same program:
+//!
+//! ```text
+//! ; Comments start with a semicolon
+//! start:  in [value]            ; labels end with a colon
+//!         add [value], #5, rb+2 ; operands are [position], #immediate, rb+relative
+//!         jt #1, #start
+//!    10:  halt                  ; a number with a colon asserts the current address
+//! value:  data 0, start+1, -3   ; data cells can also use labels
+//! ```
+//!
+//! Operand values may be integers, labels, or sums and differences of them.
+
+use std::collections::HashMap;
+
+use super::error::{AsmError, AsmErrorKind};
+use super::{Instruction, Mode, Opcode, Operand};
+
+/// A single term in an expression; a literal or a label
+#[derive(Debug, Clone)]
+enum Term {
+    Literal(isize),
+    Label(String),
+}
+
+/// A sum of terms, each of which may be negated. Evaluated after all labels
+/// are known.
+#[derive(Debug, Clone)]
+struct Expr {
+    column: usize,
+    terms: Vec<(bool, Term, usize)>,
+}
+
+impl Expr {
+    fn evaluate(&self, line: usize, labels: &HashMap<String, usize>) -> Result<isize, AsmError> {
+        self.terms
+            .iter()
+            .try_fold(0isize, |total, (negated, term, column)| {
+                let error = |kind| AsmError {
+                    line,
+                    column: *column,
+                    kind,
+                };
+
+                let value = match term {
+                    Term::Literal(value) => *value,
+                    Term::Label(name) => *labels
+                        .get(name)
+                        .ok_or_else(|| error(AsmErrorKind::UndefinedLabel(name.clone())))?
+                        as isize,
+                };
+
+                let result = if *negated {
+                    total.checked_sub(value)
+                } else {
+                    total.checked_add(value)
+                };
+
+                result.ok_or_else(|| error(AsmErrorKind::Overflow))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Op {
+        opcode: Opcode,
+        operands: Vec<(Mode, Expr)>,
+    },
+    Data(Vec<Expr>),
+}
+
+/// A cursor over a single line of source
+struct Cursor<'a> {
+    line: usize,
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn column(&self) -> usize {
+        self.text[..self.pos].chars().count() + 1
+    }
+
+    fn error(&self, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            line: self.line,
+            column: self.column(),
+            kind,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    /// Consume the given character, if it's next
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, what: &'static str) -> Result<(), AsmError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(AsmErrorKind::Expected(what)))
+        }
+    }
+
+    /// Consume a run of characters matching the predicate
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            }
+            _ => "",
+        }
+    }
+
+    /// Parse a term: an unsigned integer or a label
+    fn term(&mut self, negated: bool) -> Result<Term, AsmError> {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let column = self.column();
+                let digits = self.take_while(|c| c.is_ascii_digit());
+
+                // Parse the sign along with the digits, so that isize::MIN
+                // can be written as a literal
+                let signed = if negated {
+                    format!("-{}", digits)
+                } else {
+                    digits.to_string()
+                };
+
+                signed.parse().map(Term::Literal).map_err(|_| AsmError {
+                    line: self.line,
+                    column,
+                    kind: AsmErrorKind::InvalidNumber(digits.to_string()),
+                })
+            }
+            _ => match self.identifier() {
+                "" => Err(self.error(AsmErrorKind::Expected("a number or label"))),
+                name => Ok(Term::Label(name.to_string())),
+            },
+        }
+    }
+
+    /// Parse an expression: a series of terms joined by + or -, with an
+    /// optional leading sign.
+    fn expr(&mut self) -> Result<Expr, AsmError> {
+        self.skip_whitespace();
+        let column = self.column();
+        let mut terms = Vec::new();
+        let mut negated = if self.eat('-') {
+            true
+        } else {
+            self.eat('+');
+            false
+        };
+
+        loop {
+            let term_column = self.column();
+            let term = self.term(negated)?;
+
+            // Literals are parsed along with their sign, so only labels need
+            // to be negated during evaluation
+            let negate_label = match term {
+                Term::Literal(..) => false,
+                Term::Label(..) => negated,
+            };
+
+            terms.push((negate_label, term, term_column));
+
+            negated = if self.eat('-') {
+                true
+            } else if self.eat('+') {
+                false
+            } else {
+                break Ok(Expr { column, terms });
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<(Mode, Expr), AsmError> {
+        if self.eat('[') {
+            let expr = self.expr()?;
+            self.expect(']', "]")?;
+            Ok((Mode::Position, expr))
+        } else if self.eat('#') {
+            Ok((Mode::Immediate, self.expr()?))
+        } else if self.rest().starts_with("rb") {
+            self.pos += 2;
+            match self.peek() {
+                Some('+') | Some('-') => Ok((Mode::Relative, self.expr()?)),
+                _ => Err(self.error(AsmErrorKind::Expected("+ or - after rb"))),
+            }
+        } else {
+            Err(self.error(AsmErrorKind::Expected("an operand")))
+        }
+    }
+}
+
+/// Assemble intcode source into memory cells, suitable for `Machine::new`.
+pub fn assemble(source: &str) -> Result<Vec<isize>, AsmError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut statements: Vec<(usize, Statement)> = Vec::new();
+    let mut address = 0;
+
+    // First pass: parse every line and lay out addresses and labels
+    for (index, line) in source.lines().enumerate() {
+        let line = match line.find(';') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+
+        let mut cursor = Cursor {
+            line: index + 1,
+            text: line,
+            pos: 0,
+        };
+
+        // Labels and address markers
+        loop {
+            cursor.skip_whitespace();
+            let start = cursor.pos;
+            let column = cursor.column();
+
+            match cursor.peek() {
+                Some(c) if c.is_ascii_digit() => {
+                    let digits = cursor.take_while(|c| c.is_ascii_digit());
+                    if !cursor.eat(':') {
+                        return Err(cursor.error(AsmErrorKind::Expected(":")));
+                    }
+                    let found: usize = digits.parse().map_err(|_| AsmError {
+                        line: cursor.line,
+                        column,
+                        kind: AsmErrorKind::InvalidNumber(digits.to_string()),
+                    })?;
+                    if found != address {
+                        return Err(AsmError {
+                            line: cursor.line,
+                            column,
+                            kind: AsmErrorKind::AddressMismatch {
+                                expected: address,
+                                found,
+                            },
+                        });
+                    }
+                }
+                _ => {
+                    let name = cursor.identifier();
+                    if name.is_empty() || !cursor.eat(':') {
+                        cursor.pos = start;
+                        break;
+                    }
+                    if labels.insert(name.to_string(), address).is_some() {
+                        return Err(AsmError {
+                            line: cursor.line,
+                            column,
+                            kind: AsmErrorKind::DuplicateLabel(name.to_string()),
+                        });
+                    }
+                }
+            }
+        }
+
+        if cursor.at_end() {
+            continue;
+        }
+
+        let column = cursor.column();
+        let mnemonic = cursor.identifier();
+
+        let statement = if mnemonic == "data" {
+            let mut values = vec![cursor.expr()?];
+            while cursor.eat(',') {
+                values.push(cursor.expr()?);
+            }
+            Statement::Data(values)
+        } else {
+            let opcode = Opcode::from_mnemonic(mnemonic).ok_or_else(|| AsmError {
+                line: cursor.line,
+                column,
+                kind: AsmErrorKind::UnknownMnemonic(mnemonic.to_string()),
+            })?;
+
+            let mut operands = Vec::new();
+            if !cursor.at_end() {
+                loop {
+                    operands.push(cursor.operand()?);
+                    if !cursor.eat(',') {
+                        break;
+                    }
+                }
+            }
+
+            if operands.len() != opcode.param_count() {
+                return Err(AsmError {
+                    line: cursor.line,
+                    column,
+                    kind: AsmErrorKind::OperandCount {
+                        mnemonic: opcode.mnemonic(),
+                        expected: opcode.param_count(),
+                        found: operands.len(),
+                    },
+                });
+            }
+
+            if let Some(index) = opcode.written_param() {
+                let (mode, expr) = &operands[index - 1];
+                if *mode == Mode::Immediate {
+                    return Err(AsmError {
+                        line: cursor.line,
+                        column: expr.column,
+                        kind: AsmErrorKind::ImmediateWrite { param: index },
+                    });
+                }
+            }
+
+            Statement::Op { opcode, operands }
+        };
+
+        if !cursor.at_end() {
+            return Err(cursor.error(AsmErrorKind::Expected("end of line")));
+        }
+
+        address += match &statement {
+            Statement::Op { operands, .. } => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        };
+
+        statements.push((cursor.line, statement));
+    }
+
+    // Second pass: resolve labels and encode
+    let mut memory = Vec::with_capacity(address);
+
+    for (line, statement) in statements {
+        match statement {
+            Statement::Data(values) => {
+                for value in values {
+                    memory.push(value.evaluate(line, &labels)?);
+                }
+            }
+            Statement::Op { opcode, operands } => {
+                let operands = operands
+                    .into_iter()
+                    .map(|(mode, expr)| {
+                        expr.evaluate(line, &labels)
+                            .map(|value| Operand { mode, value })
+                    })
+                    .collect::<Result<Vec<Operand>, AsmError>>()?;
+
+                memory.extend(Instruction::Op { opcode, operands }.encode());
+            }
+        }
+    }
+
+    Ok(memory)
+}
@@ -0,0 +1,212 @@
+//! An interpreter backed by a cache of decoded instructions. `step` decodes
+//! each instruction through a chain of combinators, recomputing the opcode
+//! and parameter modes for every operand it touches; the cached interpreter
+//! splits them apart once per address and dispatches on the result.
+//!
+//! Each cache entry remembers the instruction cell it was decoded from, and
+//! is only used if that cell still holds the same value. Only the opcode and
+//! modes are cached (operands are always read from memory), so this makes
+//! the cache exact even for self-modifying programs, and even if memory is
+//! changed from outside the interpreter. A cache can also be shared between
+//! machines; this is useful when the same program is run many times.
+//! The cached interpreter only runs machines with `isize` words.
+
+use std::convert::TryFrom;
+
+use super::isa::Operands;
+use super::{opcode, parameter_mode, Machine, MachineState, Memory, Mode, Opcode};
+
+/// Instructions beyond this address are decoded on every step, rather than
+/// growing the cache to cover them
+const MAX_CACHED_ADDRESS: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy)]
+struct Decoded {
+    /// The instruction cell this was decoded from
+    instruction: isize,
+    opcode: Opcode,
+    modes: [Mode; 3],
+}
+
+impl Decoded {
+    /// Decode an instruction. Panics with the same messages as `step` if the
+    /// opcode, or the mode of any of its parameters, is invalid.
+    fn new(ip: usize, instruction: isize) -> Self {
+        let opcode = Opcode::from_code(opcode(instruction))
+            .unwrap_or_else(|| panic!("Invalid opcode at address {}: {}", ip, instruction));
+
+        let mut modes = [Mode::Position; 3];
+        for (index, mode) in (1..=opcode.param_count()).zip(modes.iter_mut()) {
+            *mode = Mode::from_digit(parameter_mode(instruction, index)).unwrap_or_else(|| {
+                panic!("Invalid opcode mode at address {}: {}", ip, instruction)
+            });
+        }
+
+        Decoded {
+            instruction,
+            opcode,
+            modes,
+        }
+    }
+}
+
+fn to_address(value: isize) -> usize {
+    usize::try_from(value).unwrap_or_else(|_| panic!("Invalid address: {}", value))
+}
+
+/// Offset the machine's relative base. Panics on overflow, like `step`.
+#[inline(always)]
+fn relative<M: Memory<Word = isize>>(machine: &Machine<M>, offset: isize) -> isize {
+    machine
+        .relative_base
+        .checked_add(offset)
+        .unwrap_or_else(|| {
+            panic!(
+                "Relative base overflow: {} + {}",
+                machine.relative_base, offset
+            )
+        })
+}
+
+/// The address of the index'th parameter of the decoded instruction at the
+/// machine's IP
+#[inline(always)]
+fn param_address<M: Memory<Word = isize>>(
+    machine: &Machine<M>,
+    decoded: &Decoded,
+    index: usize,
+) -> usize {
+    let cell = machine.instruction_pointer + index;
+
+    match decoded.modes[index - 1] {
+        Mode::Position => to_address(machine.memory.load(cell)),
+        Mode::Immediate => cell,
+        Mode::Relative => to_address(relative(machine, machine.memory.load(cell))),
+    }
+}
+
+impl<M: Memory<Word = isize>> Operands<M> for Decoded {
+    #[inline(always)]
+    fn value(&self, machine: &Machine<M>, index: usize) -> isize {
+        machine.memory.load(self.address(machine, index))
+    }
+
+    #[inline(always)]
+    fn address(&self, machine: &Machine<M>, index: usize) -> usize {
+        param_address(machine, self, index)
+    }
+}
+
+/// A cache of decoded instructions, keyed by address
+#[derive(Debug, Clone, Default)]
+pub struct DecodeCache {
+    entries: Vec<Option<Decoded>>,
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discard all cached instructions
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    /// Get the decoded instruction at the machine's IP, decoding and caching
+    /// it if it isn't already cached, or if the cached entry is stale
+    #[inline]
+    fn decode<M: Memory<Word = isize>>(&mut self, machine: &Machine<M>) -> Decoded {
+        let ip = machine.instruction_pointer;
+        let instruction = machine.memory.load(ip);
+
+        if let Some(Some(decoded)) = self.entries.get(ip) {
+            if decoded.instruction == instruction {
+                return *decoded;
+            }
+        }
+
+        let decoded = Decoded::new(ip, instruction);
+
+        if ip < MAX_CACHED_ADDRESS {
+            if ip >= self.entries.len() {
+                self.entries.resize(ip + 1, None);
+            }
+            self.entries[ip] = Some(decoded);
+        }
+
+        decoded
+    }
+
+    /// Run a single instruction of the machine. This has exactly the same
+    /// behavior as `step`, including its panics: both run the instruction
+    /// through `OpcodeSpec::plan`, differing only in how they find its
+    /// parameters.
+    #[inline]
+    fn execute<M: Memory<Word = isize>>(
+        &mut self,
+        machine: &mut Machine<M>,
+        input: &mut impl Iterator<Item = isize>,
+    ) -> Option<MachineState> {
+        let decoded = self.decode(machine);
+        let spec = decoded.opcode.spec();
+
+        let effect = spec
+            .plan(machine, &decoded)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        spec.apply(machine, effect, input)
+    }
+
+    /// Create an operation that runs a single instruction of the machine,
+    /// like `step`, using this cache
+    pub fn step<'a, M: Memory<Word = isize>>(
+        &'a mut self,
+        input: impl IntoIterator<Item = isize> + 'a,
+    ) -> impl FnMut(&mut Machine<M>) -> Option<MachineState> + 'a {
+        let mut input = input.into_iter();
+
+        move |machine| self.execute(machine, &mut input)
+    }
+
+    /// Create an operation that runs a machine until it blocks, like
+    /// `run_until_block`, using this cache
+    pub fn run_until_block<'a, M: Memory<Word = isize>>(
+        &'a mut self,
+        input: impl IntoIterator<Item = isize> + 'a,
+    ) -> impl FnMut(&mut Machine<M>) -> MachineState + 'a {
+        let mut input = input.into_iter();
+
+        move |machine| loop {
+            if let Some(state) = self.execute(machine, &mut input) {
+                break state;
+            }
+        }
+    }
+}
+
+/// Create an operation that runs a single instruction of the machine, like
+/// `step`, with its own decode cache
+pub fn step_cached<M: Memory<Word = isize>>(
+    input: impl IntoIterator<Item = isize>,
+) -> impl FnMut(&mut Machine<M>) -> Option<MachineState> {
+    let mut cache = DecodeCache::new();
+    let mut input = input.into_iter();
+
+    move |machine| cache.execute(machine, &mut input)
+}
+
+/// Create an operation that runs a machine until it blocks, like
+/// `run_until_block`, with its own decode cache
+pub fn run_until_block_cached<M: Memory<Word = isize>>(
+    input: impl IntoIterator<Item = isize>,
+) -> impl FnMut(&mut Machine<M>) -> MachineState {
+    let mut cache = DecodeCache::new();
+    let mut input = input.into_iter();
+
+    move |machine| loop {
+        if let Some(state) = cache.execute(machine, &mut input) {
+            break state;
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Code coverage. A `Coverage` is an `Observer` which records which cells of
+//! memory were run as instructions, read as operands, or written, and which
+//! were run after being written (self-modifying code):
+//!
+//! ```ignore
+//! let listing = machine.disassemble();
+//! let mut coverage = Coverage::new();
+//! run_observed(input, &mut coverage)(&mut machine)?;
+//!
+//! for region in coverage.regions(&machine) {
+//!     println!("{}", region);
+//! }
+//! print!("{}", coverage.annotate(&listing));
+//! ```
+//!
+//! From this each cell is classified as code, a table (read but never
+//! written), or scratch space (written). The classification only reflects
+//! what the observed runs did; code on a branch that was never taken looks
+//! the same as unused memory.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use super::{Instruction, Listing, Machine, Memory, Observer, Opcode, Operand, Word};
+
+/// What happened to a single memory cell during a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CellCoverage {
+    /// An instruction started at this cell
+    pub started: bool,
+
+    /// This cell was part of an instruction that was run: its opcode or
+    /// one of its parameters
+    pub executed: bool,
+
+    /// This cell was read as an operand
+    pub read: bool,
+
+    /// This cell was written by an instruction
+    pub written: bool,
+
+    /// This cell was part of an instruction that was run after the cell was
+    /// written
+    pub self_modified: bool,
+}
+
+impl CellCoverage {
+    pub fn kind(&self) -> CellKind {
+        if self.executed {
+            CellKind::Code
+        } else if self.written {
+            CellKind::Scratch
+        } else if self.read {
+            CellKind::Table
+        } else {
+            CellKind::Unused
+        }
+    }
+
+    fn merge(self, other: CellCoverage) -> CellCoverage {
+        CellCoverage {
+            started: self.started || other.started,
+            executed: self.executed || other.executed,
+            read: self.read || other.read,
+            written: self.written || other.written,
+            self_modified: self.self_modified || other.self_modified,
+        }
+    }
+}
+
+/// Flags for the cell, like `xrw!`: executed, read, written, and
+/// self-modified, with `-` for each that didn't happen.
+impl Display for CellCoverage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let flag = |set, c| if set { c } else { '-' };
+
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(self.executed, 'x'),
+            flag(self.read, 'r'),
+            flag(self.written, 'w'),
+            flag(self.self_modified, '!'),
+        )
+    }
+}
+
+/// How a memory cell was used. Code takes precedence: a cell which was run
+/// is code even if it was also read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellKind {
+    /// Part of an instruction that was run
+    Code,
+
+    /// Read as an operand, but never written
+    Table,
+
+    /// Written as an operand
+    Scratch,
+
+    /// Never touched
+    Unused,
+}
+
+impl CellKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CellKind::Code => "code",
+            CellKind::Table => "table",
+            CellKind::Scratch => "scratch",
+            CellKind::Unused => "unused",
+        }
+    }
+}
+
+impl Display for CellKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// A run of adjacent cells of the same kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub start: usize,
+
+    /// The address after the last cell of the region
+    pub end: usize,
+    pub kind: CellKind,
+
+    /// Some cell in the region is self-modified code
+    pub self_modified: bool,
+}
+
+impl Region {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}..{:<5} {:>5} cells  {}",
+            self.start,
+            self.end,
+            self.len(),
+            self.kind
+        )?;
+
+        if self.self_modified {
+            f.write_str(" (self-modifying)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An `Observer` which tracks code coverage. See the module documentation
+/// for details.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    cells: HashMap<usize, CellCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discard everything recorded so far
+    pub fn clear(&mut self) {
+        self.cells.clear()
+    }
+
+    /// What happened to the cell at this address
+    pub fn cell(&self, address: usize) -> CellCoverage {
+        self.cells.get(&address).copied().unwrap_or_default()
+    }
+
+    pub fn kind(&self, address: usize) -> CellKind {
+        self.cell(address).kind()
+    }
+
+    fn addresses(&self, test: impl Fn(&CellCoverage) -> bool) -> Vec<usize> {
+        let mut addresses: Vec<usize> = self
+            .cells
+            .iter()
+            .filter(|(_, cell)| test(cell))
+            .map(|(&address, _)| address)
+            .collect();
+
+        addresses.sort();
+        addresses
+    }
+
+    /// The addresses where instructions started, in order
+    pub fn instruction_starts(&self) -> Vec<usize> {
+        self.addresses(|cell| cell.started)
+    }
+
+    /// The addresses that were read as operands, in order
+    pub fn read_cells(&self) -> Vec<usize> {
+        self.addresses(|cell| cell.read)
+    }
+
+    /// The addresses that were written, in order
+    pub fn written_cells(&self) -> Vec<usize> {
+        self.addresses(|cell| cell.written)
+    }
+
+    /// The addresses of cells that were run after being written, in order
+    pub fn self_modified_cells(&self) -> Vec<usize> {
+        self.addresses(|cell| cell.self_modified)
+    }
+
+    /// Divide a machine's memory into regions of code, tables, scratch space,
+    /// and unused cells. The regions cover all of the machine's memory, and
+    /// continue up to the last cell that was touched if it's past the end.
+    pub fn regions<M: Memory>(&self, machine: &Machine<M>) -> Vec<Region> {
+        let mut regions: Vec<Region> = Vec::new();
+
+        // Only touched cells are stored, so the gaps between them are unused
+        let mut push = |start: usize, end: usize, cell: CellCoverage| match regions.last_mut() {
+            Some(region) if region.kind == cell.kind() && region.end == start => {
+                region.end = end;
+                region.self_modified |= cell.self_modified;
+            }
+            _ => regions.push(Region {
+                start,
+                end,
+                kind: cell.kind(),
+                self_modified: cell.self_modified,
+            }),
+        };
+
+        let mut next = 0;
+
+        for address in self.addresses(|_| true) {
+            if address > next {
+                push(next, address, CellCoverage::default());
+            }
+
+            push(address, address + 1, self.cell(address));
+            next = address + 1;
+        }
+
+        let len = machine.memory.len();
+        if len > next {
+            push(next, len, CellCoverage::default());
+        }
+
+        regions
+    }
+
+    /// Annotate each line of a listing with the coverage of its cells
+    pub fn annotate<'a, W>(&self, listing: &'a Listing<W>) -> CoverageListing<'a, W> {
+        CoverageListing {
+            lines: listing
+                .instructions
+                .iter()
+                .map(|(address, instruction)| {
+                    let coverage = (*address..*address + instruction.len())
+                        .map(|cell| self.cell(cell))
+                        .fold(CellCoverage::default(), CellCoverage::merge);
+
+                    AnnotatedLine {
+                        address: *address,
+                        instruction,
+                        coverage,
+                        kind: self.kind(*address),
+                        aligned: !coverage.executed || self.cell(*address).started,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Coverage doesn't depend on the values in memory, so it works with any word
+impl<W> Observer<W> for Coverage {
+    fn instruction(&mut self, ip: usize, _opcode: Opcode, operands: &[Operand<W>]) {
+        for address in ip..=ip + operands.len() {
+            let cell = self.cells.entry(address).or_default();
+            cell.executed = true;
+            cell.self_modified |= cell.written;
+        }
+
+        self.cells.entry(ip).or_default().started = true;
+    }
+
+    fn read(&mut self, address: usize, _value: W) {
+        self.cells.entry(address).or_default().read = true;
+    }
+
+    fn write(&mut self, address: usize, _old: W, _new: W) {
+        self.cells.entry(address).or_default().written = true;
+    }
+}
+
+/// A line of a `CoverageListing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnnotatedLine<'a, W = isize> {
+    pub address: usize,
+    pub instruction: &'a Instruction<W>,
+
+    /// The combined coverage of all of the instruction's cells
+    pub coverage: CellCoverage,
+
+    /// The kind of the instruction's first cell
+    pub kind: CellKind,
+
+    /// False if the instruction's cells were run, but not as this
+    /// instruction: the listing decoded them from a different starting
+    /// address than the running program did.
+    pub aligned: bool,
+}
+
+impl<W: Word> Display for AnnotatedLine<'_, W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}: {} {:<7} {}",
+            self.address, self.coverage, self.kind, self.instruction
+        )?;
+
+        if !self.aligned {
+            f.write_str("  ; misaligned")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A disassembly listing annotated with coverage, from `Coverage::annotate`.
+/// Each line shows the coverage flags of the instruction's cells (see
+/// `CellCoverage`) and the kind of its first cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageListing<'a, W = isize> {
+    pub lines: Vec<AnnotatedLine<'a, W>>,
+}
+
+impl<W: Word> Display for CoverageListing<'_, W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.lines
+            .iter()
+            .try_for_each(|line| writeln!(f, "{}", line))
+    }
+}
@@ -0,0 +1,338 @@
+//! A debugger wrapper around a machine, with breakpoints, watchpoints, and
+//! arbitrary break conditions built from `Value` combinators.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+
+use super::{
+    step_observed, Addressed, IntcodeError, Machine, MachineState, Memory, Observer, Value, Word,
+    IP,
+};
+
+/// A condition on the machine's effects that causes execution to break
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watchpoint<W = isize> {
+    /// Break when the memory cell at this address changes
+    Memory(usize),
+
+    /// Break when the relative base crosses this value (that is, when it
+    /// moves from below it to at or above it, or vice versa)
+    RelativeBase(isize),
+
+    /// Break when the machine outputs this value
+    Output(W),
+}
+
+/// The reason the debugger stopped execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason<W = isize> {
+    /// The IP reached a breakpoint
+    Breakpoint(usize),
+
+    /// A watched memory cell changed
+    MemoryChanged { address: usize, old: W, new: W },
+
+    /// The relative base crossed a watched value
+    RelativeBaseCrossed {
+        threshold: isize,
+        old: isize,
+        new: isize,
+    },
+
+    /// The machine output a watched value
+    Output(W),
+
+    /// A break condition became true. Includes the ID returned by
+    /// `add_condition`.
+    Condition(usize),
+}
+
+impl<W: Display> Display for BreakReason<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            BreakReason::Breakpoint(address) => write!(f, "breakpoint at {}", address),
+            BreakReason::MemoryChanged { address, old, new } => {
+                write!(f, "memory at {} changed from {} to {}", address, old, new)
+            }
+            BreakReason::RelativeBaseCrossed {
+                threshold,
+                old,
+                new,
+            } => write!(
+                f,
+                "relative base crossed {} (from {} to {})",
+                threshold, old, new
+            ),
+            BreakReason::Output(value) => write!(f, "output {}", value),
+            BreakReason::Condition(id) => write!(f, "condition {} became true", id),
+        }
+    }
+}
+
+/// The state of a machine stopped by the debugger. This mirrors
+/// `MachineState`, with an additional `Break` state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugState<W = isize> {
+    Output(W),
+    NeedInput,
+    Halt,
+
+    /// Execution broke. If the instruction that caused the break also
+    /// output a value, it's included here, so that it isn't lost.
+    Break {
+        reason: BreakReason<W>,
+        output: Option<W>,
+    },
+}
+
+impl<W> From<MachineState<W>> for DebugState<W> {
+    fn from(state: MachineState<W>) -> Self {
+        match state {
+            MachineState::Output(value) => DebugState::Output(value),
+            MachineState::NeedInput => DebugState::NeedInput,
+            MachineState::Halt => DebugState::Halt,
+        }
+    }
+}
+
+type Check<M> = Box<dyn Fn(&Machine<M>) -> bool>;
+
+struct Condition<M> {
+    id: usize,
+    check: Check<M>,
+    previous: bool,
+}
+
+/// Observer that checks each step against the watchpoints, recording the
+/// first one that is hit.
+struct WatchObserver<'a, W> {
+    watchpoints: &'a [Watchpoint<W>],
+    hit: Option<BreakReason<W>>,
+}
+
+impl<W> WatchObserver<'_, W> {
+    fn hit(&mut self, reason: BreakReason<W>) {
+        self.hit.get_or_insert(reason);
+    }
+}
+
+impl<W: Word> Observer<W> for WatchObserver<'_, W> {
+    fn write(&mut self, address: usize, old: W, new: W) {
+        if old != new && self.watchpoints.contains(&Watchpoint::Memory(address)) {
+            self.hit(BreakReason::MemoryChanged { address, old, new });
+        }
+    }
+
+    fn relative_base(&mut self, old: isize, new: isize) {
+        for watchpoint in self.watchpoints {
+            if let Watchpoint::RelativeBase(threshold) = *watchpoint {
+                if (old < threshold) != (new < threshold) {
+                    self.hit(BreakReason::RelativeBaseCrossed {
+                        threshold,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+    }
+
+    fn output(&mut self, value: W) {
+        if self
+            .watchpoints
+            .contains(&Watchpoint::Output(value.clone()))
+        {
+            self.hit(BreakReason::Output(value));
+        }
+    }
+}
+
+/// A machine wrapped with debugging state: breakpoints, watchpoints, break
+/// conditions, and a queue of pending input.
+pub struct Debugger<M: Memory = Vec<isize>> {
+    machine: Machine<M>,
+    input: VecDeque<M::Word>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint<M::Word>>,
+    conditions: Vec<Condition<M>>,
+    next_condition_id: usize,
+
+    /// If we just stopped at a breakpoint, its address, so that we can
+    /// resume past it
+    stopped_at: Option<usize>,
+}
+
+impl<M: Memory> Debugger<M> {
+    pub fn new(machine: Machine<M>) -> Self {
+        Debugger {
+            machine,
+            input: VecDeque::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            next_condition_id: 0,
+            stopped_at: None,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<M> {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine<M> {
+        &mut self.machine
+    }
+
+    pub fn into_machine(self) -> Machine<M> {
+        self.machine
+    }
+
+    /// The current instruction pointer
+    pub fn ip(&self) -> usize {
+        IP.address(&self.machine)
+    }
+
+    /// The current relative base
+    pub fn relative_base(&self) -> isize {
+        self.machine.relative_base
+    }
+
+    /// Add values to the end of the pending input queue
+    pub fn push_input(&mut self, values: impl IntoIterator<Item = M::Word>) {
+        self.input.extend(values)
+    }
+
+    /// The input values which haven't been consumed yet
+    pub fn pending_input(&self) -> &VecDeque<M::Word> {
+        &self.input
+    }
+
+    /// Add a breakpoint. Returns false if there was already a breakpoint at
+    /// this address.
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Remove a breakpoint. Returns false if there was no breakpoint at this
+    /// address.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint<M::Word>) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint)
+        }
+    }
+
+    /// Remove a watchpoint. Returns false if there was no such watchpoint.
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint<M::Word>) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| *w != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint<M::Word>] {
+        &self.watchpoints
+    }
+
+    /// Add a break condition, like `address(100).map(|v| v > 5)`. Execution
+    /// breaks after any instruction that causes the condition to go from
+    /// false to true. Returns an ID for the condition, which is included in
+    /// the break reason and can be used to remove it.
+    pub fn add_condition(
+        &mut self,
+        condition: impl Value<M::Word, Output = bool> + 'static,
+    ) -> usize {
+        let id = self.next_condition_id;
+        self.next_condition_id += 1;
+
+        let previous = condition.get(&self.machine);
+        self.conditions.push(Condition {
+            id,
+            check: Box::new(move |machine| condition.get(machine)),
+            previous,
+        });
+
+        id
+    }
+
+    /// Remove a break condition. Returns false if there was no condition with
+    /// this ID.
+    pub fn remove_condition(&mut self, id: usize) -> bool {
+        let len = self.conditions.len();
+        self.conditions.retain(|condition| condition.id != id);
+        self.conditions.len() != len
+    }
+
+    /// Run a single instruction, ignoring breakpoints but checking watchpoints
+    /// and conditions. Returns None if the machine didn't stop.
+    pub fn step(&mut self) -> Result<Option<DebugState<M::Word>>, IntcodeError<M::Word>> {
+        let Debugger {
+            machine,
+            input,
+            watchpoints,
+            conditions,
+            ..
+        } = self;
+
+        let mut observer = WatchObserver {
+            watchpoints,
+            hit: None,
+        };
+
+        let state = step_observed(iter::from_fn(|| input.pop_front()), &mut observer)(machine)?;
+
+        // Once an instruction has run, we're no longer stopped at a breakpoint
+        if state != Some(MachineState::NeedInput) {
+            self.stopped_at = None;
+        }
+
+        let mut hit = observer.hit;
+
+        for condition in conditions.iter_mut() {
+            let current = (condition.check)(machine);
+            if current && !condition.previous {
+                hit.get_or_insert(BreakReason::Condition(condition.id));
+            }
+            condition.previous = current;
+        }
+
+        Ok(match hit {
+            Some(reason) => Some(DebugState::Break {
+                reason,
+                output: match state {
+                    Some(MachineState::Output(value)) => Some(value),
+                    _ => None,
+                },
+            }),
+            None => state.map(DebugState::from),
+        })
+    }
+
+    /// Run the machine until it outputs, blocks on input, halts, or hits a
+    /// breakpoint, watchpoint, or condition. If the machine is stopped at a
+    /// breakpoint, that breakpoint is ignored, so that execution can be
+    /// resumed after hitting one.
+    pub fn run(&mut self) -> Result<DebugState<M::Word>, IntcodeError<M::Word>> {
+        loop {
+            let ip = self.ip();
+            if self.breakpoints.contains(&ip) && self.stopped_at != Some(ip) {
+                self.stopped_at = Some(ip);
+                break Ok(DebugState::Break {
+                    reason: BreakReason::Breakpoint(ip),
+                    output: None,
+                });
+            }
+
+            if let Some(state) = self.step()? {
+                break Ok(state);
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! A disassembler for intcode programs. Decoding follows the same opcode and
+//! parameter mode rules as `step`, but works directly on a memory slice
+//! rather than on a running machine.
+
+use std::fmt::{self, Display, Formatter};
+
+use super::{address, opcode, parameter_mode, Addressed, Machine, Memory, Opcode, Value, Word, IP};
+
+/// A parameter mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    /// Get the mode for a mode digit, if it's valid
+    pub fn from_digit(digit: isize) -> Option<Self> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn digit(self) -> isize {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+/// A decoded instruction parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Operand<W = isize> {
+    pub mode: Mode,
+    pub value: W,
+}
+
+impl<W: Word> Display for Operand<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            // Skip the minus sign, rather than negating, which could overflow
+            Mode::Relative if self.value.is_negative() => {
+                write!(f, "rb-{}", &self.value.to_string()[1..])
+            }
+            Mode::Relative => write!(f, "rb+{}", self.value),
+        }
+    }
+}
+
+/// A single decoded cell or instruction from a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction<W = isize> {
+    Op {
+        opcode: Opcode,
+        operands: Vec<Operand<W>>,
+    },
+
+    /// A cell that doesn't decode as an instruction
+    Data(W),
+}
+
+impl<W> Instruction<W> {
+    /// The number of memory cells this instruction occupies
+    pub fn len(&self) -> usize {
+        match self {
+            Instruction::Op { operands, .. } => operands.len() + 1,
+            Instruction::Data(..) => 1,
+        }
+    }
+}
+
+impl<W: Word> Instruction<W> {
+    /// Encode this instruction back into memory cells
+    pub fn encode(&self) -> Vec<W> {
+        match self {
+            Instruction::Data(value) => vec![value.clone()],
+            Instruction::Op { opcode, operands } => {
+                let modes = operands
+                    .iter()
+                    .rev()
+                    .fold(0, |modes, operand| modes * 10 + operand.mode.digit());
+
+                let mut cells = vec![W::from_isize(modes * 100 + opcode.code())];
+                cells.extend(operands.iter().map(|operand| operand.value.clone()));
+                cells
+            }
+        }
+    }
+}
+
+impl<W: Word> Display for Instruction<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Instruction::Data(value) => write!(f, "data {}", value),
+            Instruction::Op { opcode, operands } => {
+                f.write_str(opcode.mnemonic())?;
+
+                for (i, operand) in operands.iter().enumerate() {
+                    f.write_str(if i == 0 { " " } else { ", " })?;
+                    write!(f, "{}", operand)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Decode the instruction at the given address. Returns None if the cell
+/// doesn't hold a valid instruction: if its opcode or any of its modes are
+/// invalid, if it writes through an immediate parameter, if its parameters
+/// run past the end of memory, or if it has mode digits beyond its last
+/// parameter (which `step` ignores, but which wouldn't survive re-encoding).
+pub fn decode<W: Word>(memory: &[W], address: usize) -> Option<Instruction<W>> {
+    decode_exact(|address| memory.get(address).cloned(), address)
+}
+
+/// Decode the instruction at the current IP of a running machine. Unlike
+/// `decode`, cells past the end of memory are read as 0, and extra mode
+/// digits are ignored, as they are by `step`.
+pub fn decode_at_ip<M: Memory>(machine: &Machine<M>) -> Option<Instruction<M::Word>> {
+    decode_with(|cell| Some(address(cell).get(machine)), IP.address(machine))
+}
+
+/// Decode the instruction at the given address, using `fetch` to read memory.
+/// The instruction cell itself must fit in an `isize`.
+fn decode_with<W: Word>(
+    fetch: impl Fn(usize) -> Option<W>,
+    address: usize,
+) -> Option<Instruction<W>> {
+    let instruction = fetch(address)?.to_isize()?;
+    let opcode = Opcode::from_code(opcode(instruction))?;
+
+    let operands = (1..=opcode.param_count())
+        .map(|index| {
+            let mode = Mode::from_digit(parameter_mode(instruction, index))?;
+            if mode == Mode::Immediate && opcode.written_param() == Some(index) {
+                return None;
+            }
+            let value = fetch(address + index)?;
+            Some(Operand { mode, value })
+        })
+        .collect::<Option<Vec<Operand<W>>>>()?;
+
+    Some(Instruction::Op { opcode, operands })
+}
+
+/// Decode the instruction at the given address, like `decode_with`, but only
+/// if it encodes back to exactly the same instruction cell
+fn decode_exact<W: Word>(
+    fetch: impl Fn(usize) -> Option<W>,
+    address: usize,
+) -> Option<Instruction<W>> {
+    let instruction = decode_with(&fetch, address)?;
+
+    if instruction.encode().first() == fetch(address).as_ref() {
+        Some(instruction)
+    } else {
+        None
+    }
+}
+
+/// A disassembled program: a list of instructions and their addresses
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Listing<W = isize> {
+    pub instructions: Vec<(usize, Instruction<W>)>,
+}
+
+impl<W> Listing<W> {
+    /// Find the instruction that starts at the given address
+    pub fn at(&self, address: usize) -> Option<&Instruction<W>> {
+        self.instructions
+            .binary_search_by_key(&address, |&(addr, _)| addr)
+            .ok()
+            .map(|index| &self.instructions[index].1)
+    }
+}
+
+impl<W: Word> Display for Listing<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.instructions
+            .iter()
+            .try_for_each(|(address, instruction)| writeln!(f, "{:>5}: {}", address, instruction))
+    }
+}
+
+/// Disassemble a section of memory, starting at `start`. Each cell is decoded
+/// as an instruction if possible, or as data if not; decoding then resumes
+/// after the instruction or data cell.
+pub fn disassemble_from<W: Word>(memory: &[W], start: usize) -> Listing<W> {
+    disassemble_with(|address| memory.get(address).cloned(), memory.len(), start)
+}
+
+/// Disassemble a whole program
+pub fn disassemble<W: Word>(memory: &[W]) -> Listing<W> {
+    disassemble_from(memory, 0)
+}
+
+/// Disassemble the cells from `start` up to `len`, using `fetch` to read
+/// memory
+fn disassemble_with<W: Word>(
+    fetch: impl Fn(usize) -> Option<W>,
+    len: usize,
+    start: usize,
+) -> Listing<W> {
+    let mut instructions = Vec::new();
+    let mut address = start;
+
+    while address < len {
+        let instruction = decode_exact(&fetch, address)
+            .unwrap_or_else(|| Instruction::Data(fetch(address).unwrap_or_default()));
+        let len = instruction.len();
+        instructions.push((address, instruction));
+        address += len;
+    }
+
+    Listing { instructions }
+}
+
+impl<M: Memory> Machine<M> {
+    /// Disassemble this machine's memory
+    pub fn disassemble(&self) -> Listing<M::Word> {
+        self.disassemble_from(0)
+    }
+
+    /// Disassemble this machine's memory, starting at the given address
+    pub fn disassemble_from(&self, start: usize) -> Listing<M::Word> {
+        let len = self.memory.len();
+        let fetch = |address| {
+            if address < len {
+                Some(self.memory.load(address))
+            } else {
+                None
+            }
+        };
+
+        disassemble_with(fetch, len, start)
+    }
+}
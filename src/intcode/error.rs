@@ -0,0 +1,368 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::num::ParseIntError;
+
+/// An error encountered while executing an intcode instruction. Each variant
+/// carries the instruction pointer and the raw instruction at that location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntcodeError<W = isize> {
+    /// The instruction's opcode isn't one we know how to run
+    InvalidOpcode { ip: usize, instruction: W },
+
+    /// One of the instruction's parameters has an unknown mode
+    InvalidMode {
+        ip: usize,
+        instruction: W,
+        param: usize,
+    },
+
+    /// A parameter (or jump target) resolved to a negative address
+    NegativeAddress {
+        ip: usize,
+        instruction: W,
+        address: W,
+    },
+
+    /// A parameter (or jump target) resolved to an address too large to
+    /// index memory. Only possible with words wider than `isize`.
+    AddressTooLarge {
+        ip: usize,
+        instruction: W,
+        address: W,
+    },
+
+    /// The instruction tried to write through an immediate mode parameter
+    ImmediateWrite {
+        ip: usize,
+        instruction: W,
+        param: usize,
+    },
+
+    /// Adding an offset to the relative base overflowed, either to find the
+    /// address of a relative mode parameter or to adjust the relative base
+    RelativeBaseOverflow {
+        ip: usize,
+        instruction: W,
+        relative_base: isize,
+        offset: W,
+    },
+
+    /// The machine needed input, but none was available
+    InputExhausted { ip: usize, instruction: W },
+
+    /// An arithmetic instruction overflowed, under `OverflowPolicy::Checked`
+    Overflow {
+        ip: usize,
+        instruction: W,
+        lhs: W,
+        rhs: W,
+    },
+}
+
+impl<W: Clone> IntcodeError<W> {
+    /// The address of the instruction that caused the error
+    pub fn ip(&self) -> usize {
+        match *self {
+            IntcodeError::InvalidOpcode { ip, .. }
+            | IntcodeError::InvalidMode { ip, .. }
+            | IntcodeError::NegativeAddress { ip, .. }
+            | IntcodeError::AddressTooLarge { ip, .. }
+            | IntcodeError::ImmediateWrite { ip, .. }
+            | IntcodeError::RelativeBaseOverflow { ip, .. }
+            | IntcodeError::InputExhausted { ip, .. }
+            | IntcodeError::Overflow { ip, .. } => ip,
+        }
+    }
+
+    /// The raw instruction that caused the error
+    pub fn instruction(&self) -> W {
+        match self {
+            IntcodeError::InvalidOpcode { instruction, .. }
+            | IntcodeError::InvalidMode { instruction, .. }
+            | IntcodeError::NegativeAddress { instruction, .. }
+            | IntcodeError::AddressTooLarge { instruction, .. }
+            | IntcodeError::ImmediateWrite { instruction, .. }
+            | IntcodeError::RelativeBaseOverflow { instruction, .. }
+            | IntcodeError::InputExhausted { instruction, .. }
+            | IntcodeError::Overflow { instruction, .. } => instruction.clone(),
+        }
+    }
+}
+
+impl<W: Display> Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::InvalidOpcode { ip, instruction } => {
+                write!(f, "Invalid opcode at address {}: {}", ip, instruction)
+            }
+            IntcodeError::InvalidMode {
+                ip,
+                instruction,
+                param,
+            } => write!(
+                f,
+                "Invalid mode for parameter {} at address {}: {}",
+                param, ip, instruction
+            ),
+            IntcodeError::NegativeAddress {
+                ip,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "Negative address {} used by instruction at address {}: {}",
+                address, ip, instruction
+            ),
+            IntcodeError::AddressTooLarge {
+                ip,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "Address {} too large, used by instruction at address {}: {}",
+                address, ip, instruction
+            ),
+            IntcodeError::ImmediateWrite {
+                ip,
+                instruction,
+                param,
+            } => write!(
+                f,
+                "Write through immediate parameter {} at address {}: {}",
+                param, ip, instruction
+            ),
+            IntcodeError::RelativeBaseOverflow {
+                ip,
+                instruction,
+                relative_base,
+                offset,
+            } => write!(
+                f,
+                "Relative base overflow of {} + {} at address {}: {}",
+                relative_base, offset, ip, instruction
+            ),
+            IntcodeError::InputExhausted { ip, instruction } => write!(
+                f,
+                "Unexpected end of input at address {}: {}",
+                ip, instruction
+            ),
+            IntcodeError::Overflow {
+                ip,
+                instruction,
+                lhs,
+                rhs,
+            } => write!(
+                f,
+                "Arithmetic overflow of {} and {} at address {}: {}",
+                lhs, rhs, ip, instruction
+            ),
+        }
+    }
+}
+
+impl<W: Debug + Display> Error for IntcodeError<W> {}
+
+/// An error storing a value in machine memory: the address is one that no
+/// memory can hold, since its length would overflow a `usize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryError {
+    pub address: usize,
+}
+
+impl Display for MemoryError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Address {} is past the end of memory", self.address)
+    }
+}
+
+impl Error for MemoryError {}
+
+/// An error parsing a comma-separated intcode program. Includes the index of
+/// the bad cell and the byte offset of its token in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramParseError<E = ParseIntError> {
+    pub cell: usize,
+    pub offset: usize,
+    pub token: String,
+    pub error: E,
+}
+
+impl<E: Display> Display for ProgramParseError<E> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid value {:?} for cell {} at byte offset {}: {}",
+            self.token, self.cell, self.offset, self.error
+        )
+    }
+}
+
+impl<E: Error + 'static> Error for ProgramParseError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error loading an intcode program from a file or reader
+#[derive(Debug)]
+pub enum ProgramLoadError {
+    Io(io::Error),
+    Parse(ProgramParseError),
+}
+
+impl From<io::Error> for ProgramLoadError {
+    fn from(err: io::Error) -> Self {
+        ProgramLoadError::Io(err)
+    }
+}
+
+impl From<ProgramParseError> for ProgramLoadError {
+    fn from(err: ProgramParseError) -> Self {
+        ProgramLoadError::Parse(err)
+    }
+}
+
+impl Display for ProgramLoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ProgramLoadError::Io(err) => write!(f, "Error reading program: {}", err),
+            ProgramLoadError::Parse(err) => write!(f, "Error parsing program: {}", err),
+        }
+    }
+}
+
+impl Error for ProgramLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProgramLoadError::Io(err) => Some(err),
+            ProgramLoadError::Parse(err) => Some(err),
+        }
+    }
+}
+
+/// The kind of error encountered while assembling an intcode program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    /// Something other than what was expected was found
+    Expected(&'static str),
+    UnknownMnemonic(String),
+    InvalidNumber(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+
+    /// An instruction was given the wrong number of operands
+    OperandCount {
+        mnemonic: &'static str,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A written operand was given in immediate mode
+    ImmediateWrite {
+        param: usize,
+    },
+
+    /// An address marker (like `12:`) didn't match the current address
+    AddressMismatch {
+        expected: usize,
+        found: usize,
+    },
+
+    /// An expression overflowed while being evaluated
+    Overflow,
+}
+
+/// An error assembling an intcode program, with the (1-based) line and
+/// column where it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl Display for AsmErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AsmErrorKind::Expected(what) => write!(f, "expected {}", what),
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic {:?}", name),
+            AsmErrorKind::InvalidNumber(digits) => write!(f, "invalid number {:?}", digits),
+            AsmErrorKind::UndefinedLabel(name) => write!(f, "undefined label {:?}", name),
+            AsmErrorKind::DuplicateLabel(name) => write!(f, "duplicate label {:?}", name),
+            AsmErrorKind::OperandCount {
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} operands, but {} were given",
+                mnemonic, expected, found
+            ),
+            AsmErrorKind::ImmediateWrite { param } => {
+                write!(f, "operand {} is written, so it can't be immediate", param)
+            }
+            AsmErrorKind::AddressMismatch { expected, found } => write!(
+                f,
+                "address marker {} doesn't match the current address {}",
+                found, expected
+            ),
+            AsmErrorKind::Overflow => write!(f, "expression overflowed"),
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for AsmError {}
+
+/// An error loading a machine snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+
+    /// The file doesn't start with the snapshot magic bytes
+    BadMagic,
+
+    /// The snapshot was written by an incompatible version of the format
+    UnsupportedVersion(u32),
+
+    /// The file ended before the snapshot was complete
+    Truncated,
+
+    /// A value in the snapshot doesn't fit in this platform's integers
+    OutOfRange,
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "Error reading snapshot: {}", err),
+            SnapshotError::BadMagic => write!(f, "Not an intcode snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported snapshot version: {}", version)
+            }
+            SnapshotError::Truncated => write!(f, "Snapshot is truncated"),
+            SnapshotError::OutOfRange => write!(f, "Snapshot value is out of range"),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
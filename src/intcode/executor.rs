@@ -0,0 +1,244 @@
+//! A minimal single-threaded executor for driving `MachineStream`s, along
+//! with a local channel for feeding them input. This is enough to run
+//! networks of machines without an external async runtime.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread::{self, Thread};
+
+use futures_core::stream::Stream;
+
+/// Wakeup state shared between a task and its wakers
+#[derive(Debug)]
+struct Notify {
+    woken: AtomicBool,
+    thread: Thread,
+}
+
+impl Notify {
+    /// Create a new notifier for the current thread. It starts out woken, so
+    /// that its task is polled at least once.
+    fn new() -> Arc<Self> {
+        Arc::new(Notify {
+            woken: AtomicBool::new(true),
+            thread: thread::current(),
+        })
+    }
+
+    fn notify(&self) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.thread.unpark();
+    }
+
+    /// Check if the task has been woken since the last call, and reset it
+    fn take(&self) -> bool {
+        self.woken.swap(false, Ordering::SeqCst)
+    }
+
+    fn waker(self: &Arc<Self>) -> Waker {
+        unsafe { Waker::from_raw(raw_waker(self.clone())) }
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_waker);
+
+fn raw_waker(notify: Arc<Notify>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(notify) as *const (), &VTABLE)
+}
+
+unsafe fn clone(data: *const ()) -> RawWaker {
+    let notify = Arc::from_raw(data as *const Notify);
+    let cloned = notify.clone();
+    mem::forget(notify);
+    raw_waker(cloned)
+}
+
+unsafe fn wake(data: *const ()) {
+    Arc::from_raw(data as *const Notify).notify()
+}
+
+unsafe fn wake_by_ref(data: *const ()) {
+    (*(data as *const Notify)).notify()
+}
+
+unsafe fn drop_waker(data: *const ()) {
+    drop(Arc::from_raw(data as *const Notify))
+}
+
+/// Run a future to completion on the current thread, parking the thread
+/// while it waits to be woken.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let notify = Notify::new();
+    let waker = notify.waker();
+    let mut ctx = Context::from_waker(&waker);
+
+    loop {
+        if notify.take() {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut ctx) {
+                break output;
+            }
+        } else {
+            thread::park();
+        }
+    }
+}
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    notify: Arc<Notify>,
+}
+
+/// A single-threaded executor for many tasks. Tasks are polled in the order
+/// they were spawned, and only when they've been woken.
+#[derive(Default)]
+pub struct LocalExecutor {
+    tasks: Vec<Task>,
+}
+
+impl LocalExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a task to the executor. It won't be run until `run` is called.
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'static) {
+        self.tasks.push(Task {
+            future: Box::pin(future),
+            notify: Notify::new(),
+        })
+    }
+
+    /// The number of tasks which haven't completed
+    pub fn task_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Run tasks until they have all completed, or until every remaining
+    /// task is waiting on something that hasn't happened. Returns the number
+    /// of remaining tasks; spawn more tasks or provide more input (for
+    /// instance, through a `Sender`) and call `run` again to resume them.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let mut progress = false;
+            let mut index = 0;
+
+            while index < self.tasks.len() {
+                let task = &mut self.tasks[index];
+
+                if task.notify.take() {
+                    progress = true;
+                    let waker = task.notify.waker();
+                    let mut ctx = Context::from_waker(&waker);
+
+                    if task.future.as_mut().poll(&mut ctx).is_ready() {
+                        self.tasks.remove(index);
+                        continue;
+                    }
+                }
+
+                index += 1;
+            }
+
+            if !progress {
+                break self.tasks.len();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    queue: VecDeque<isize>,
+    waker: Option<Waker>,
+    senders: usize,
+}
+
+impl Shared {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake()
+        }
+    }
+}
+
+/// The sending half of a local channel. The channel is closed when all of
+/// its senders are dropped.
+#[derive(Debug)]
+pub struct Sender {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Sender {
+    pub fn send(&self, value: isize) {
+        let mut shared = self.shared.borrow_mut();
+        shared.queue.push_back(value);
+        shared.wake();
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.borrow_mut().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            shared.wake();
+        }
+    }
+}
+
+/// The receiving half of a local channel. It's a stream of the sent values,
+/// which ends when the channel is closed and empty.
+#[derive(Debug)]
+pub struct Receiver {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Stream for Receiver {
+    type Item = isize;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<isize>> {
+        let mut shared = self.shared.borrow_mut();
+
+        match shared.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if shared.senders == 0 => Poll::Ready(None),
+            None => {
+                shared.waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Create an unbounded channel for use within a single thread, such as
+/// between tasks in a `LocalExecutor`.
+pub fn channel() -> (Sender, Receiver) {
+    let shared = Rc::new(RefCell::new(Shared {
+        senders: 1,
+        ..Shared::default()
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
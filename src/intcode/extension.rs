@@ -0,0 +1,224 @@
+//! Extension opcodes for experimental intcode variants. Extra instructions,
+//! such as a debug print or a host-provided random number, are registered
+//! with an `Extensions` set, each with a handler closure, and then run by
+//! `step_extended` alongside the standard instruction set:
+//!
+//! ```ignore
+//! let mut extensions = Extensions::new();
+//!
+//! // dbg a: print a parameter
+//! extensions.register(50, 1, |machine| {
+//!     eprintln!("dbg: {}", param(1).get(machine));
+//!     ExtensionFlow::Advance(None)
+//! });
+//!
+//! // rand a: write a random number to a parameter
+//! extensions.register(51, 1, move |machine| {
+//!     set(rng.next(), param(1))(machine);
+//!     ExtensionFlow::Advance(None)
+//! });
+//!
+//! // spin: run this instruction forever
+//! extensions.register(52, 0, |_machine| ExtensionFlow::Stay(None));
+//!
+//! let mut stepper = step_extended(input, extensions);
+//! ```
+//!
+//! Handlers read and write their parameters through `param(n)`, so the
+//! usual parameter modes apply. Opcodes which are neither standard nor
+//! registered still panic in `step_extended`, or return an error from
+//! `try_step_extended`.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+
+use super::{
+    advance_ip, check_instruction, check_parameters, Addressed, IntcodeError, Machine,
+    MachineState, Memory, Opcode, StepResult, Value, IP,
+};
+
+/// What happens to the IP after an extension handler runs, and the state the
+/// machine stopped in, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionFlow<W = isize> {
+    /// Advance the IP past the instruction. The handler must not have moved
+    /// the IP itself.
+    Advance(Option<MachineState<W>>),
+
+    /// Leave the IP where the handler left it: either somewhere the handler
+    /// jumped to (which may be the instruction itself, to spin or retry), or
+    /// still on the instruction, as with `in` when it blocks and `halt`.
+    Stay(Option<MachineState<W>>),
+}
+
+type Handler<'a, M> = Box<dyn FnMut(&mut Machine<M>) -> ExtensionFlow<<M as Memory>::Word> + 'a>;
+
+struct Extension<'a, M: Memory> {
+    param_count: usize,
+    handler: Handler<'a, M>,
+}
+
+/// A set of extension opcodes and their handlers. See the module
+/// documentation for details.
+pub struct Extensions<'a, M: Memory = Vec<isize>> {
+    extensions: HashMap<isize, Extension<'a, M>>,
+}
+
+impl<'a, M: Memory> Extensions<'a, M> {
+    pub fn new() -> Self {
+        Extensions {
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Register an extension opcode which takes `param_count` parameters.
+    /// When the machine runs the opcode, the handler is called with the IP
+    /// still pointing at the instruction, so that it can use `param(n)`.
+    /// Afterwards, the IP is advanced past the instruction or left alone,
+    /// depending on the `ExtensionFlow` the handler returns.
+    ///
+    /// Panics if the opcode is a standard opcode, is already registered, or
+    /// isn't a valid opcode (0 to 99).
+    pub fn register(
+        &mut self,
+        code: isize,
+        param_count: usize,
+        handler: impl FnMut(&mut Machine<M>) -> ExtensionFlow<M::Word> + 'a,
+    ) {
+        assert!(
+            (0..100).contains(&code),
+            "Extension opcode must be between 0 and 99: {}",
+            code
+        );
+
+        if let Some(standard) = Opcode::from_code(code) {
+            panic!("Opcode {} is already the standard `{}`", code, standard);
+        }
+
+        let extension = Extension {
+            param_count,
+            handler: Box::new(handler),
+        };
+
+        if self.extensions.insert(code, extension).is_some() {
+            panic!("Extension opcode {} is already registered", code);
+        }
+    }
+
+    /// True if this opcode has been registered
+    pub fn contains(&self, code: isize) -> bool {
+        self.extensions.contains_key(&code)
+    }
+
+    /// The number of parameters a registered opcode takes
+    pub fn param_count(&self, code: isize) -> Option<usize> {
+        self.extensions
+            .get(&code)
+            .map(|extension| extension.param_count)
+    }
+
+    /// Run the extension instruction at the machine's IP, if it's registered
+    fn execute(
+        &mut self,
+        machine: &mut Machine<M>,
+        code: isize,
+    ) -> Option<Option<MachineState<M::Word>>> {
+        let extension = self.extensions.get_mut(&code)?;
+
+        match (extension.handler)(machine) {
+            ExtensionFlow::Advance(state) => {
+                advance_ip(extension.param_count + 1)(machine);
+                Some(state)
+            }
+            ExtensionFlow::Stay(state) => Some(state),
+        }
+    }
+}
+
+impl<M: Memory> Default for Extensions<'_, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Memory> Debug for Extensions<'_, M> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut codes: Vec<_> = self.extensions.keys().collect();
+        codes.sort();
+
+        f.debug_struct("Extensions").field("codes", &codes).finish()
+    }
+}
+
+/// Create an operation that runs a single instruction of the machine, like
+/// `step`, but which also runs the registered extension opcodes.
+pub fn step_extended<'a, M: Memory + 'a>(
+    input: impl IntoIterator<Item = M::Word> + 'a,
+    mut extensions: Extensions<'a, M>,
+) -> impl FnMut(&mut Machine<M>) -> Option<MachineState<M::Word>> + 'a {
+    let mut input = input.into_iter();
+
+    move |machine| {
+        let code = machine.opcode();
+
+        match code.and_then(Opcode::from_code) {
+            Some(code) => code.spec().execute(machine, &mut input),
+            None => code
+                .and_then(|code| extensions.execute(machine, code))
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Invalid opcode at address {}: {}",
+                        IP.address(machine),
+                        IP.get(machine),
+                    )
+                }),
+        }
+    }
+}
+
+/// Create an operation that runs a single instruction of the machine, like
+/// `try_step`, but which also runs the registered extension opcodes. Standard
+/// instructions are checked with `check_instruction`; for extension
+/// instructions, only the modes and addresses of their parameters are
+/// checked before the handler runs.
+pub fn try_step_extended<'a, M: Memory + 'a>(
+    input: impl IntoIterator<Item = M::Word> + 'a,
+    mut extensions: Extensions<'a, M>,
+) -> impl FnMut(&mut Machine<M>) -> StepResult<M::Word> + 'a {
+    let mut input = input.into_iter();
+
+    move |machine| {
+        let code = machine.opcode();
+
+        if let Some(code) = code.and_then(Opcode::from_code) {
+            check_instruction(machine)?;
+            return Ok(code.spec().execute(machine, &mut input));
+        }
+
+        match code.and_then(|code| Some((code, extensions.param_count(code)?))) {
+            Some((code, param_count)) => {
+                check_parameters(machine, param_count, None)?;
+                Ok(extensions.execute(machine, code).unwrap_or(None))
+            }
+            None => Err(IntcodeError::InvalidOpcode {
+                ip: IP.address(machine),
+                instruction: IP.get(machine),
+            }),
+        }
+    }
+}
+
+/// Create an operation that runs a machine until it blocks, like
+/// `run_until_block`, but which also runs the registered extension opcodes.
+pub fn run_until_block_extended<'a, M: Memory + 'a>(
+    input: impl IntoIterator<Item = M::Word> + 'a,
+    extensions: Extensions<'a, M>,
+) -> impl FnMut(&mut Machine<M>) -> MachineState<M::Word> + 'a {
+    let mut stepper = step_extended(input, extensions);
+
+    move |machine| loop {
+        if let Some(state) = stepper(machine) {
+            break state;
+        }
+    }
+}
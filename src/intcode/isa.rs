@@ -0,0 +1,435 @@
+//! The intcode instruction set, described in a single table. Each opcode's
+//! number, mnemonic, parameters, and semantics are defined here and nowhere
+//! else; the disassembler and the assembler read them from `INSTRUCTION_SET`,
+//! and `step`, the cached interpreter, and `check_instruction` all run
+//! instructions through `OpcodeSpec::plan`, so they can't disagree about what
+//! an instruction means.
+
+use std::fmt::{self, Display, Formatter};
+
+use super::{
+    param, Addressed, IntcodeError, Machine, MachineState, Memory, OverflowPolicy, Value, Word, IP,
+};
+
+/// The computation done by a binary instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Mul,
+    LessThan,
+    Equals,
+}
+
+impl BinaryOp {
+    /// Compute the result, handling overflow according to the policy.
+    /// Returns None if it overflows under the `Checked` policy.
+    #[inline(always)]
+    pub fn apply<W: Word>(self, a: &W, b: &W, policy: OverflowPolicy) -> Option<W> {
+        match self {
+            BinaryOp::Add => policy.add(a, b),
+            BinaryOp::Mul => policy.mul(a, b),
+            BinaryOp::LessThan => Some(W::from_isize(if a < b { 1 } else { 0 })),
+            BinaryOp::Equals => Some(W::from_isize(if a == b { 1 } else { 0 })),
+        }
+    }
+}
+
+/// The condition tested by a jump instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpCondition {
+    IsTrue,
+    IsFalse,
+}
+
+impl JumpCondition {
+    #[inline(always)]
+    pub fn test<W: Word>(self, value: &W) -> bool {
+        match self {
+            JumpCondition::IsTrue => !value.is_zero(),
+            JumpCondition::IsFalse => value.is_zero(),
+        }
+    }
+}
+
+/// What an instruction does, in terms of its parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Semantics {
+    /// Compute a value from parameters 1 and 2, and write it to parameter 3
+    Binary(BinaryOp),
+
+    /// Jump to the value of parameter 2 if parameter 1 passes the condition
+    Jump(JumpCondition),
+
+    /// Read an input value into parameter 1, or block if there is none
+    Input,
+
+    /// Output the value of parameter 1
+    Output,
+
+    /// Add the value of parameter 1 to the relative base
+    AdjustRb,
+    Halt,
+}
+
+/// The description of a single opcode
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeSpec {
+    pub opcode: Opcode,
+
+    /// The numeric code; the last two digits of an instruction
+    pub code: isize,
+    pub mnemonic: &'static str,
+    pub param_count: usize,
+
+    /// The index of the parameter this opcode writes to, if any. Written
+    /// parameters can't be in immediate mode.
+    pub written_param: Option<usize>,
+    pub semantics: Semantics,
+}
+
+/// Define the `Opcode` enum, its lookups, and the `INSTRUCTION_SET` table
+/// together, from a single list of opcodes. The variants of `Opcode` are
+/// declared in the same order as the table, so `Opcode::spec` can index it by
+/// variant.
+macro_rules! instruction_set {
+    ($($opcode:ident {
+        code: $code:literal,
+        mnemonic: $mnemonic:literal,
+        param_count: $param_count:expr,
+        written_param: $written_param:expr,
+        semantics: $semantics:expr,
+    },)*) => {
+        /// A known intcode opcode. See `INSTRUCTION_SET` for their definitions.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Opcode {
+            $($opcode,)*
+        }
+
+        /// Every intcode opcode, in the same order as the variants of `Opcode`
+        pub const INSTRUCTION_SET: [OpcodeSpec; [$(Opcode::$opcode),*].len()] = [
+            $(OpcodeSpec {
+                opcode: Opcode::$opcode,
+                code: $code,
+                mnemonic: $mnemonic,
+                param_count: $param_count,
+                written_param: $written_param,
+                semantics: $semantics,
+            },)*
+        ];
+
+        impl Opcode {
+            /// Get the opcode with this numeric code, if any
+            #[inline]
+            pub fn from_code(code: isize) -> Option<Self> {
+                match code {
+                    $($code => Some(Opcode::$opcode),)*
+                    _ => None,
+                }
+            }
+
+            /// Get the opcode with this mnemonic, if any
+            pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+                match mnemonic {
+                    $($mnemonic => Some(Opcode::$opcode),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+instruction_set! {
+    Add {
+        code: 1,
+        mnemonic: "add",
+        param_count: 3,
+        written_param: Some(3),
+        semantics: Semantics::Binary(BinaryOp::Add),
+    },
+    Mul {
+        code: 2,
+        mnemonic: "mul",
+        param_count: 3,
+        written_param: Some(3),
+        semantics: Semantics::Binary(BinaryOp::Mul),
+    },
+    Input {
+        code: 3,
+        mnemonic: "in",
+        param_count: 1,
+        written_param: Some(1),
+        semantics: Semantics::Input,
+    },
+    Output {
+        code: 4,
+        mnemonic: "out",
+        param_count: 1,
+        written_param: None,
+        semantics: Semantics::Output,
+    },
+    JumpIfTrue {
+        code: 5,
+        mnemonic: "jt",
+        param_count: 2,
+        written_param: None,
+        semantics: Semantics::Jump(JumpCondition::IsTrue),
+    },
+    JumpIfFalse {
+        code: 6,
+        mnemonic: "jf",
+        param_count: 2,
+        written_param: None,
+        semantics: Semantics::Jump(JumpCondition::IsFalse),
+    },
+    LessThan {
+        code: 7,
+        mnemonic: "lt",
+        param_count: 3,
+        written_param: Some(3),
+        semantics: Semantics::Binary(BinaryOp::LessThan),
+    },
+    Equals {
+        code: 8,
+        mnemonic: "eq",
+        param_count: 3,
+        written_param: Some(3),
+        semantics: Semantics::Binary(BinaryOp::Equals),
+    },
+    AdjustRb {
+        code: 9,
+        mnemonic: "arb",
+        param_count: 1,
+        written_param: None,
+        semantics: Semantics::AdjustRb,
+    },
+    Halt {
+        code: 99,
+        mnemonic: "halt",
+        param_count: 0,
+        written_param: None,
+        semantics: Semantics::Halt,
+    },
+}
+
+impl Opcode {
+    /// The description of this opcode in the instruction set table. Both are
+    /// generated by `instruction_set!`, so the variants are in table order.
+    #[inline]
+    pub fn spec(self) -> &'static OpcodeSpec {
+        &INSTRUCTION_SET[self as usize]
+    }
+
+    /// The numeric code of this opcode; the last two digits of an instruction
+    pub fn code(self) -> isize {
+        self.spec().code
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        self.spec().mnemonic
+    }
+
+    /// The number of parameters this opcode takes
+    pub fn param_count(self) -> usize {
+        self.spec().param_count
+    }
+
+    /// The index of the parameter this opcode writes to, if any
+    pub fn written_param(self) -> Option<usize> {
+        self.spec().written_param
+    }
+
+    pub fn semantics(self) -> Semantics {
+        self.spec().semantics
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// How an instruction finds its parameters. The semantics of each opcode are
+/// written once, in `OpcodeSpec::plan`, in terms of this trait: `step` and
+/// `check_instruction` read parameters through the `param` combinator, and
+/// the cached interpreter through its decoded modes.
+pub(super) trait Operands<M: Memory> {
+    /// The value of the index'th parameter
+    fn value(&self, machine: &Machine<M>, index: usize) -> M::Word;
+
+    /// The address of the index'th parameter. Only called for parameters
+    /// which are written.
+    fn address(&self, machine: &Machine<M>, index: usize) -> usize;
+}
+
+/// Parameters read through the `param` combinator
+pub(super) struct Params;
+
+impl<M: Memory> Operands<M> for Params {
+    #[inline(always)]
+    fn value(&self, machine: &Machine<M>, index: usize) -> M::Word {
+        param(index).get(machine)
+    }
+
+    #[inline(always)]
+    fn address(&self, machine: &Machine<M>, index: usize) -> usize {
+        param(index).address(machine)
+    }
+}
+
+/// What an instruction will do to the machine, from `OpcodeSpec::plan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Effect<W> {
+    /// Write the value to the address, then advance the IP
+    Write(usize, W),
+
+    /// Read an input value into the address and advance the IP, or block if
+    /// there is no input
+    Input(usize),
+
+    /// Output the value, then advance the IP
+    Output(W),
+
+    /// Move the IP to the address
+    Jump(usize),
+
+    /// Advance the IP without doing anything else
+    Advance,
+
+    /// Set the relative base, then advance the IP
+    SetRelativeBase(isize),
+    Halt,
+}
+
+impl OpcodeSpec {
+    /// Work out what this instruction will do at the machine's IP, without
+    /// changing the machine. Returns an error if it would overflow under the
+    /// `Checked` policy, jump to an invalid address, or overflow the relative
+    /// base. The parameters themselves aren't checked; see `check_parameters`.
+    #[inline(always)]
+    pub(super) fn plan<M: Memory>(
+        &self,
+        machine: &Machine<M>,
+        operands: &impl Operands<M>,
+    ) -> Result<Effect<M::Word>, IntcodeError<M::Word>> {
+        let error = |machine: &Machine<M>| (IP.address(machine), IP.get(machine));
+
+        Ok(match self.semantics {
+            Semantics::Binary(op) => {
+                let lhs = operands.value(machine, 1);
+                let rhs = operands.value(machine, 2);
+
+                match op.apply(&lhs, &rhs, machine.overflow) {
+                    Some(result) => Effect::Write(operands.address(machine, 3), result),
+                    None => {
+                        let (ip, instruction) = error(machine);
+                        return Err(IntcodeError::Overflow {
+                            ip,
+                            instruction,
+                            lhs,
+                            rhs,
+                        });
+                    }
+                }
+            }
+            Semantics::Jump(condition) => {
+                if condition.test(&operands.value(machine, 1)) {
+                    let target = operands.value(machine, 2);
+
+                    match target.to_address() {
+                        Some(target) => Effect::Jump(target),
+                        None => {
+                            let (ip, instruction) = error(machine);
+                            return Err(if target.is_negative() {
+                                IntcodeError::NegativeAddress {
+                                    ip,
+                                    instruction,
+                                    address: target,
+                                }
+                            } else {
+                                IntcodeError::AddressTooLarge {
+                                    ip,
+                                    instruction,
+                                    address: target,
+                                }
+                            });
+                        }
+                    }
+                } else {
+                    Effect::Advance
+                }
+            }
+            Semantics::Input => Effect::Input(operands.address(machine, 1)),
+            Semantics::Output => Effect::Output(operands.value(machine, 1)),
+            Semantics::AdjustRb => {
+                let offset = operands.value(machine, 1);
+                let relative_base = offset
+                    .to_isize()
+                    .and_then(|offset| machine.relative_base.checked_add(offset));
+
+                match relative_base {
+                    Some(relative_base) => Effect::SetRelativeBase(relative_base),
+                    None => {
+                        let (ip, instruction) = error(machine);
+                        return Err(IntcodeError::RelativeBaseOverflow {
+                            ip,
+                            instruction,
+                            relative_base: machine.relative_base,
+                            offset,
+                        });
+                    }
+                }
+            }
+            Semantics::Halt => Effect::Halt,
+        })
+    }
+
+    /// Apply the effect of this instruction, from `plan`, to the machine
+    #[inline(always)]
+    pub(super) fn apply<M: Memory>(
+        &self,
+        machine: &mut Machine<M>,
+        effect: Effect<M::Word>,
+        input: &mut impl Iterator<Item = M::Word>,
+    ) -> Option<MachineState<M::Word>> {
+        let next = machine.instruction_pointer + self.param_count + 1;
+
+        match effect {
+            Effect::Write(address, value) => machine.memory.store(address, value),
+            Effect::Input(address) => match input.next() {
+                None => return Some(MachineState::NeedInput),
+                Some(value) => machine.memory.store(address, value),
+            },
+            Effect::Output(value) => {
+                machine.instruction_pointer = next;
+                return Some(MachineState::Output(value));
+            }
+            Effect::Jump(target) => {
+                machine.instruction_pointer = target;
+                return None;
+            }
+            Effect::Advance => {}
+            Effect::SetRelativeBase(relative_base) => machine.relative_base = relative_base,
+            Effect::Halt => return Some(MachineState::Halt),
+        }
+
+        machine.instruction_pointer = next;
+        None
+    }
+
+    /// Run this instruction at the machine's IP. The instruction's modes
+    /// aren't checked, and it panics if `plan` returns an error; see
+    /// `check_instruction`.
+    #[inline]
+    pub fn execute<M: Memory>(
+        &self,
+        machine: &mut Machine<M>,
+        input: &mut impl Iterator<Item = M::Word>,
+    ) -> Option<MachineState<M::Word>> {
+        let effect = self
+            .plan(machine, &Params)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        self.apply(machine, effect, input)
+    }
+}
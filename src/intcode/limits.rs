@@ -0,0 +1,151 @@
+//! Resource limits for running machines. A limited run stops with
+//! `LimitedState::OutOfFuel` when it has used up its instruction budget, or
+//! with `LimitedState::MemoryLimit` when an instruction would write past the
+//! end of the allowed memory. In both cases the instruction is not run and
+//! the machine is left unchanged, so the run can be resumed exactly where it
+//! stopped after raising the limits.
+//!
+//! Every instruction is checked with `check_instruction` before it runs, so
+//! an invalid program stops the run with an error instead of panicking.
+
+use std::iter::Peekable;
+
+use super::{
+    check_instruction, param, Addressed, IntcodeError, Machine, MachineState, Memory, Opcode,
+    Value, IP,
+};
+
+/// Limits on a machine run. `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// The number of instructions that may still be run
+    pub fuel: Option<u64>,
+
+    /// The maximum size of the machine's memory. Instructions which would
+    /// write at or above this address are not run.
+    pub memory: Option<usize>,
+}
+
+impl Limits {
+    pub const fn with_fuel(self, fuel: u64) -> Self {
+        Limits {
+            fuel: Some(fuel),
+            ..self
+        }
+    }
+
+    pub const fn with_memory(self, memory: usize) -> Self {
+        Limits {
+            memory: Some(memory),
+            ..self
+        }
+    }
+}
+
+/// The state a limited run stopped in
+#[must_use]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitedState<W = isize> {
+    /// The machine stopped by itself, as it would in an unlimited run
+    Machine(MachineState<W>),
+
+    /// The run used up its instruction budget
+    OutOfFuel,
+
+    /// The next instruction would write past the memory limit
+    MemoryLimit,
+}
+
+/// A machine run with resource limits. Create one with `run_limited`. The
+/// limits can be inspected and changed between calls; remaining fuel is
+/// carried over from one call to the next.
+pub struct LimitedRun<I: Iterator> {
+    input: Peekable<I>,
+    pub limits: Limits,
+}
+
+impl<I: Iterator> LimitedRun<I> {
+    /// The remaining fuel, if fuel is limited
+    pub fn fuel(&self) -> Option<u64> {
+        self.limits.fuel
+    }
+
+    /// Add more fuel to the run. Has no effect if fuel is unlimited. The fuel
+    /// saturates at `u64::MAX` rather than overflowing.
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(remaining) = self.limits.fuel.as_mut() {
+            *remaining = remaining.saturating_add(fuel);
+        }
+    }
+
+    /// Run a single instruction of the machine, like `try_step`, unless
+    /// doing so would exceed a limit.
+    pub fn step<M: Memory>(
+        &mut self,
+        machine: &mut Machine<M>,
+    ) -> Result<Option<LimitedState<M::Word>>, IntcodeError<M::Word>>
+    where
+        I: Iterator<Item = M::Word>,
+    {
+        if self.limits.fuel == Some(0) {
+            return Ok(Some(LimitedState::OutOfFuel));
+        }
+
+        let code = match machine.opcode().and_then(Opcode::from_code) {
+            Some(code) => code,
+            None => {
+                return Err(IntcodeError::InvalidOpcode {
+                    ip: IP.address(machine),
+                    instruction: IP.get(machine),
+                })
+            }
+        };
+
+        check_instruction(machine)?;
+
+        // The instruction has been checked, so its written address is valid.
+        // An input instruction which is about to block doesn't write anything.
+        if let (Some(max), Some(index)) = (self.limits.memory, code.written_param()) {
+            let blocks = code == Opcode::Input && self.input.peek().is_none();
+
+            if !blocks && param(index).address(machine) >= max {
+                return Ok(Some(LimitedState::MemoryLimit));
+            }
+        }
+
+        let state = code.spec().execute(machine, &mut self.input);
+
+        // A blocked input instruction didn't run, so it doesn't use any fuel
+        if state != Some(MachineState::NeedInput) {
+            if let Some(fuel) = self.limits.fuel.as_mut() {
+                *fuel -= 1;
+            }
+        }
+
+        Ok(state.map(LimitedState::Machine))
+    }
+
+    /// Run the machine until it outputs, blocks on input, halts, reaches a
+    /// limit, or runs an invalid instruction, like `try_run_until_block`.
+    pub fn run_until_block<M: Memory>(
+        &mut self,
+        machine: &mut Machine<M>,
+    ) -> Result<LimitedState<M::Word>, IntcodeError<M::Word>>
+    where
+        I: Iterator<Item = M::Word>,
+    {
+        loop {
+            if let Some(state) = self.step(machine)? {
+                break Ok(state);
+            }
+        }
+    }
+}
+
+/// Create a limited run of a machine, with the given input
+pub fn run_limited<I: IntoIterator>(input: I, limits: Limits) -> LimitedRun<I::IntoIter> {
+    LimitedRun {
+        input: input.into_iter().peekable(),
+        limits,
+    }
+}
@@ -1,12 +1,164 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Read};
 use std::iter::FromIterator;
+use std::path::Path;
+use std::str::FromStr;
 
-use super::{Addressed, Value};
+use num::BigInt;
 
+use super::{
+    opcode, Addressed, Memory, OverflowPolicy, PagedMemory, ProgramLoadError, ProgramParseError,
+    Value, Word, IP,
+};
+
+/// An intcode machine. By default its memory is a dense `Vec<isize>`; see
+/// `Memory` for other backends, and `Word` for other word sizes.
 #[derive(Debug, Clone, Default)]
-pub struct Machine {
+pub struct Machine<M = Vec<isize>> {
     pub(super) instruction_pointer: usize,
     pub(super) relative_base: isize,
-    pub(super) memory: Vec<isize>,
+    pub(super) memory: M,
+    pub(super) overflow: OverflowPolicy,
+}
+
+impl<M: Memory> Machine<M> {
+    /// Create a new machine with some seed memory in a particular backend
+    pub fn with_memory(memory: M) -> Self {
+        Machine {
+            instruction_pointer: 0,
+            relative_base: 0,
+            memory,
+            overflow: OverflowPolicy::Wrapping,
+        }
+    }
+
+    /// Set what the machine does when an arithmetic instruction overflows
+    pub fn with_overflow(self, overflow: OverflowPolicy) -> Self {
+        Machine { overflow, ..self }
+    }
+
+    pub fn overflow(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    pub fn set_overflow(&mut self, overflow: OverflowPolicy) {
+        self.overflow = overflow;
+    }
+
+    /// Get the value described by `Value`
+    pub fn get<T: Value<M::Word>>(&self, value: T) -> T::Output {
+        value.get(self)
+    }
+
+    /// The instruction at the IP, if it fits in an `isize`. Instructions
+    /// which don't can't have a valid opcode.
+    #[inline(always)]
+    pub(super) fn instruction(&self) -> Option<isize> {
+        self.get(IP).to_isize()
+    }
+
+    /// The opcode of the instruction at the IP, if it fits in an `isize`
+    #[inline(always)]
+    pub(super) fn opcode(&self) -> Option<isize> {
+        self.instruction().map(opcode)
+    }
+}
+
+/// A machine with 64 bit words
+pub type Machine64 = Machine<Vec<i64>>;
+
+/// A machine with 128 bit words
+pub type Machine128 = Machine<Vec<i128>>;
+
+/// A machine with arbitrary-precision words
+pub type BigMachine = Machine<Vec<BigInt>>;
+
+impl<W: Word> Machine<Vec<W>> {
+    /// Read a machine with any word type from comma-separated input. Panics
+    /// if the input is invalid; see `parse_csv` for details.
+    pub fn from_words(input: &str) -> Self
+    where
+        W: FromStr,
+        W::Err: Display,
+    {
+        Self::parse_words(input)
+            .unwrap_or_else(|err| panic!("Failed to parse machine input: {}", err))
+    }
+
+    /// Read a machine with any word type from comma-separated input. See
+    /// `parse_csv` for the format.
+    pub fn parse_words(input: &str) -> Result<Self, ProgramParseError<W::Err>>
+    where
+        W: FromStr,
+    {
+        let mut memory = Vec::with_capacity(input.len() / 2);
+
+        let push_cell = |memory: &mut Vec<W>, (start, end): (usize, usize)| {
+            let token = &input[start..end];
+            let value = token.parse().map_err(|error| ProgramParseError {
+                cell: memory.len(),
+                offset: start,
+                token: token.to_string(),
+                error,
+            });
+
+            value.map(|value| memory.push(value))
+        };
+
+        // Leading and trailing commas are allowed, but an empty cell between
+        // two values is an error. It's parsed as an empty token just after its
+        // comma, once a value shows up after it.
+        let push_value = |memory: &mut Vec<W>, empty_cell: Option<usize>, span| {
+            if let Some(offset) = empty_cell {
+                push_cell(memory, (offset, offset))?;
+            }
+            push_cell(memory, span)
+        };
+
+        // The span of the text in the current cell, from its first to its last
+        // non-whitespace character
+        let mut cell_start = 0;
+        let mut span: Option<(usize, usize)> = None;
+        let mut empty_cell = None;
+        let mut in_comment = false;
+
+        for (index, c) in input.char_indices() {
+            if in_comment {
+                in_comment = c != '\n';
+            } else if c == '#' {
+                in_comment = true;
+            } else if c == ',' {
+                match span.take() {
+                    Some(span) => push_value(&mut memory, empty_cell, span)?,
+                    None if memory.is_empty() => {}
+                    None => {
+                        empty_cell.get_or_insert(cell_start);
+                    }
+                }
+                cell_start = index + 1;
+            } else if !c.is_whitespace() {
+                let end = index + c.len_utf8();
+                span = Some(span.map_or((index, end), |(start, _)| (start, end)));
+            }
+        }
+
+        if let Some(span) = span {
+            push_value(&mut memory, empty_cell, span)?;
+        }
+
+        Ok(Self::with_memory(memory))
+    }
+
+    /// Convert this machine to use paged memory
+    pub fn into_paged(self) -> Machine<PagedMemory<W>> {
+        Machine {
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            memory: self.memory.into(),
+            overflow: self.overflow,
+        }
+    }
 }
 
 impl Machine {
@@ -16,6 +168,7 @@ impl Machine {
             instruction_pointer: 0,
             relative_base: 0,
             memory,
+            overflow: OverflowPolicy::Wrapping,
         }
     }
 
@@ -24,28 +177,45 @@ impl Machine {
         Self::new(Vec::new())
     }
 
-    /// Read a machine from comma-separated input
+    /// Read a machine from comma-separated input. Panics if the input is
+    /// invalid; see `parse_csv` for details.
     pub fn from_csv(input: &str) -> Self {
-        input
-            .trim()
-            .trim_matches(',')
-            .split(',')
-            .map(|value| value.parse().expect("Failed to parse machine input"))
-            .collect()
+        Self::parse_csv(input)
+            .unwrap_or_else(|err| panic!("Failed to parse machine input: {}", err))
     }
 
-    /// Get the value described by `Value`
-    pub fn get<T: Value>(&self, value: T) -> T::Output {
-        value.get(self)
+    /// Read a machine from comma-separated input. Cells are separated by
+    /// commas, and may be surrounded by any whitespace (including newlines); a
+    /// `#` starts a comment that runs to the end of the line. Leading and
+    /// trailing commas are ignored, as they were by `from_csv`. Returns an
+    /// error describing the first cell that isn't a valid integer, including
+    /// any empty cell between two values.
+    pub fn parse_csv(input: &str) -> Result<Self, ProgramParseError> {
+        Self::parse_words(input)
+    }
+
+    /// Read a machine from comma-separated input in a reader. See `parse_csv`
+    /// for the format.
+    pub fn read_csv(mut reader: impl Read) -> Result<Self, ProgramLoadError> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        Ok(Self::parse_csv(&input)?)
+    }
+
+    /// Read a machine from a comma-separated file. See `parse_csv` for the
+    /// format.
+    pub fn load_csv(path: impl AsRef<Path>) -> Result<Self, ProgramLoadError> {
+        let file = File::open(path)?;
+        Self::read_csv(io::BufReader::new(file))
     }
 }
 
-impl FromIterator<isize> for Machine {
-    fn from_iter<I: IntoIterator<Item = isize>>(iter: I) -> Self {
-        Self::new(iter.into_iter().collect())
+impl<W: Word> FromIterator<W> for Machine<Vec<W>> {
+    fn from_iter<I: IntoIterator<Item = W>>(iter: I) -> Self {
+        Self::with_memory(iter.into_iter().collect())
     }
 }
 
-pub fn initialize_to(init: Machine) -> impl Fn(&mut Machine) {
+pub fn initialize_to<M: Memory>(init: Machine<M>) -> impl Fn(&mut Machine<M>) {
     move |machine| machine.clone_from(&init)
 }
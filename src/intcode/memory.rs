@@ -0,0 +1,141 @@
+//! Storage backends for machine memory. Cells which have never been written
+//! always read as 0.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::iter::FromIterator;
+
+use super::{MemoryError, Word};
+
+/// A storage backend for a machine's memory.
+pub trait Memory: Debug + Clone + Default {
+    /// The type of each cell
+    type Word: Word;
+
+    /// Get the value of a cell. Unwritten cells are 0.
+    fn load(&self, address: usize) -> Self::Word;
+
+    /// Set the value of a cell, allocating storage for it if necessary.
+    /// Returns an error if the address is too large for any memory to hold.
+    fn try_store(&mut self, address: usize, value: Self::Word) -> Result<(), MemoryError>;
+
+    /// Set the value of a cell, like `try_store`. Panics if the address is
+    /// too large.
+    #[inline(always)]
+    fn store(&mut self, address: usize, value: Self::Word) {
+        self.try_store(address, value)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// One past the highest address that has been allocated. All cells at
+    /// or above this address are 0.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Dense memory: a single contiguous vector, grown as needed to hold the
+/// highest written address. This is the default, and the fastest for typical
+/// programs, but a single write to a very large address will allocate all
+/// the memory below it.
+impl<W: Word> Memory for Vec<W> {
+    type Word = W;
+
+    #[inline(always)]
+    fn load(&self, address: usize) -> W {
+        self.as_slice().get(address).cloned().unwrap_or_default()
+    }
+
+    #[inline]
+    fn try_store(&mut self, address: usize, value: W) -> Result<(), MemoryError> {
+        if address >= self.len() {
+            let len = address.checked_add(1).ok_or(MemoryError { address })?;
+
+            // This correctly reserves ambitiously to prevent frequent
+            // allocations.
+            self.resize_with(len, Default::default);
+        }
+
+        self[address] = value;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+const PAGE_SIZE: usize = 1024;
+
+/// Paged memory: fixed-size pages, allocated only when a cell in them is
+/// written. Suitable for programs that write to very large addresses.
+#[derive(Debug, Clone, Default)]
+pub struct PagedMemory<W = isize> {
+    pages: HashMap<usize, Box<[W]>>,
+    len: usize,
+}
+
+impl<W: Word> PagedMemory<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of pages that have been allocated
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl<W: Word> Memory for PagedMemory<W> {
+    type Word = W;
+
+    #[inline]
+    fn load(&self, address: usize) -> W {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map(|page| page[address % PAGE_SIZE].clone())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    fn try_store(&mut self, address: usize, value: W) -> Result<(), MemoryError> {
+        let len = address.checked_add(1).ok_or(MemoryError { address })?;
+
+        // Unallocated pages already read as 0
+        let page = match self.pages.get_mut(&(address / PAGE_SIZE)) {
+            Some(page) => page,
+            None if value.is_zero() => return Ok(()),
+            None => self
+                .pages
+                .entry(address / PAGE_SIZE)
+                .or_insert_with(|| vec![W::default(); PAGE_SIZE].into_boxed_slice()),
+        };
+
+        page[address % PAGE_SIZE] = value;
+        self.len = self.len.max(len);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<W: Word> From<Vec<W>> for PagedMemory<W> {
+    fn from(cells: Vec<W>) -> Self {
+        cells.into_iter().collect()
+    }
+}
+
+impl<W: Word> FromIterator<W> for PagedMemory<W> {
+    fn from_iter<I: IntoIterator<Item = W>>(iter: I) -> Self {
+        let mut memory = PagedMemory::new();
+        for (address, value) in iter.into_iter().enumerate() {
+            memory.store(address, value);
+        }
+        memory
+    }
+}
@@ -2,96 +2,215 @@
  * A compositional intcode machine builder. This also defines the spec
  * for running a single step of the machine.
  */
+pub mod ascii;
+pub mod asm;
+pub mod cache;
+pub mod coverage;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod executor;
+pub mod extension;
+pub mod isa;
+pub mod limits;
 pub mod machine;
+pub mod memory;
+pub mod network;
+pub mod observe;
 pub mod operation;
+pub mod profile;
+pub mod scheduler;
+pub mod snapshot;
+pub mod stream;
+pub mod threaded;
 pub mod value;
+pub mod word;
 
 use std::fmt::Debug;
 use std::iter;
-use std::thread;
 
-use crossbeam::channel;
+use isa::Params;
 
-use crate::proc;
-pub use machine::{initialize_to, Machine};
+pub use ascii::{AsciiEvent, AsciiLine, AsciiMachine, ReadStop, Transcript};
+pub use asm::assemble;
+pub use cache::{run_until_block_cached, step_cached, DecodeCache};
+pub use coverage::{AnnotatedLine, CellCoverage, CellKind, Coverage, CoverageListing, Region};
+pub use debugger::{BreakReason, DebugState, Debugger, Watchpoint};
+pub use disasm::{decode, decode_at_ip, disassemble, Instruction, Listing, Mode, Operand};
+pub use error::{
+    AsmError, IntcodeError, MemoryError, ProgramLoadError, ProgramParseError, SnapshotError,
+};
+pub use executor::{block_on, LocalExecutor};
+pub use extension::{
+    run_until_block_extended, step_extended, try_step_extended, ExtensionFlow, Extensions,
+};
+pub use isa::{BinaryOp, JumpCondition, Opcode, OpcodeSpec, Semantics, INSTRUCTION_SET};
+pub use limits::{run_limited, LimitedRun, LimitedState, Limits};
+pub use machine::{initialize_to, BigMachine, Machine, Machine128, Machine64};
+pub use memory::{Memory, PagedMemory};
+pub use network::{LastPacketNat, Nat, Network, NetworkEvent, Packet};
+pub use observe::{run_observed, step_observed, Observer};
 pub use operation::*;
+pub use profile::{CellAccess, HotSpot, ProfileReport, Profiler};
+pub use scheduler::{Policy, Report, Scheduler};
+pub use snapshot::Snapshot;
+pub use stream::{next, stream_iter, MachineStream};
+pub use threaded::{
+    make_threaded_machine, spawn_machine, threaded_machine, ExitReason, MachineHandle,
+    ThreadOptions,
+};
 pub use value::*;
+pub use word::{OverflowPolicy, Word};
 
-fn binary_operation(
-    compute: impl Fn(isize, isize) -> isize,
-) -> impl FnMut(&mut Machine) -> Option<MachineState> {
-    chain(
-        set(binary(param(1), param(2), compute), param(3)),
-        advance_ip(4),
-    )
-}
+/// The result of a fallible step: the state the machine stopped in, if any
+pub type StepResult<W = isize> = Result<Option<MachineState<W>>, IntcodeError<W>>;
 
-fn conditional_jmp(condition: impl Fn(isize) -> bool) -> impl FnMut(&mut Machine) {
-    set_ip(cond_address(
-        param(1).map(condition),
-        param(2).deref(),
-        IP.offset(3),
-    ))
-}
+/// The result of a fallible run: the state the machine stopped in
+pub type RunResult<W = isize> = Result<MachineState<W>, IntcodeError<W>>;
 
-/// Create an operation that runs a single instruction of the machine.
-pub fn step(
-    input: impl IntoIterator<Item = isize>,
-) -> impl FnMut(&mut Machine) -> Option<MachineState> {
-    // TODO: constify all this
+/// Create an operation that runs a single instruction of the machine, as
+/// defined by `INSTRUCTION_SET`.
+pub fn step<M: Memory>(
+    input: impl IntoIterator<Item = M::Word>,
+) -> impl FnMut(&mut Machine<M>) -> Option<MachineState<M::Word>> {
     let mut input = input.into_iter();
 
-    // Basic compute + write operations
-    let op_add = binary_operation(|a, b| a + b);
-    let op_mul = binary_operation(|a, b| a * b);
-    let op_lt = binary_operation(|a, b| if a < b { 1 } else { 0 });
-    let op_eq = binary_operation(|a, b| if a == b { 1 } else { 0 });
+    // The opcode is read once, up front, so that exactly one instruction is
+    // run per step, even though most instructions move the IP.
+    move |machine| match machine.opcode().and_then(Opcode::from_code) {
+        Some(code) => code.spec().execute(machine, &mut input),
+        None => panic!(
+            "Invalid opcode at address {}: {}",
+            IP.address(machine),
+            IP.get(machine),
+        ),
+    }
+}
 
-    // Conditional writes
-    let op_jmp_true = conditional_jmp(|c| c != 0);
-    let op_jmp_false = conditional_jmp(|c| c == 0);
+/// Check that the instruction at the current IP can be run by `step` without
+/// panicking: that its opcode and parameter modes are valid, that none of its
+/// addresses are negative or overflow, that it doesn't write through an
+/// immediate parameter, and that it doesn't overflow under the `Checked`
+/// policy.
+pub fn check_instruction<M: Memory>(machine: &Machine<M>) -> Result<(), IntcodeError<M::Word>> {
+    let ip = IP.address(machine);
+    let instruction = IP.get(machine);
 
-    // Read input
-    let op_input = move |m: &mut Machine| match input.next() {
-        None => Some(MachineState::NeedInput),
-        Some(value) => chain(set(value, param(1)), advance_ip(2))(m),
+    let code = match machine.opcode().and_then(Opcode::from_code) {
+        Some(code) => code,
+        None => return Err(IntcodeError::InvalidOpcode { ip, instruction }),
     };
 
-    // Write a value to output. The operation itself doesn't really do
-    // anything; we rely on the loop break to push the value to output.
-    let op_output = fetch_then(param(1), advance_ip(2));
-
-    // Update the relative base
-    let op_rb_offset = chain(move_rb(param(1)), advance_ip(2));
-
-    // This is a series of chained ifs, not a switch; hopefully the compiler
-    // can optimize it.
-    proc!(
-        match_opcode(1, op_add);
-        match_opcode(2, op_mul);
-        match_opcode(3, op_input);
-        match_opcode(4, op_output);
-        match_opcode(5, op_jmp_true);
-        match_opcode(6, op_jmp_false);
-        match_opcode(7, op_lt);
-        match_opcode(8, op_eq);
-        match_opcode(9, op_rb_offset);
-        match_opcode(99, |_m| MachineState::Halt);
-        |machine| match IP.map(opcode).get(machine) {
-            1..=9 | 99 => {},
-            _ => panic!("Invalid opcode at address {}: {}",
-                IP.address(machine),
-                IP.get(machine),
-            )
-        }
-    )
+    check_parameters(machine, code.param_count(), code.written_param())?;
+
+    // The parameters are valid, so planning the instruction can't panic; it
+    // finds any other errors without changing the machine
+    code.spec().plan(machine, &Params).map(|_| ())
+}
+
+/// Check the first `param_count` parameters of the instruction at the
+/// current IP: that their modes are valid, that none of their addresses are
+/// negative or overflow, and that the written parameter (if any) isn't in
+/// immediate mode.
+pub(super) fn check_parameters<M: Memory>(
+    machine: &Machine<M>,
+    param_count: usize,
+    written_param: Option<usize>,
+) -> Result<(), IntcodeError<M::Word>> {
+    let ip = IP.address(machine);
+    let instruction = IP.get(machine);
+
+    let raw_instruction = match machine.instruction() {
+        Some(raw) => raw,
+        None => return Err(IntcodeError::InvalidOpcode { ip, instruction }),
+    };
+
+    for index in 1..=param_count {
+        let raw = IP.offset(index).get(machine);
+        let mode = match Mode::from_digit(parameter_mode(raw_instruction, index)) {
+            Some(mode) => mode,
+            None => {
+                return Err(IntcodeError::InvalidMode {
+                    ip,
+                    instruction,
+                    param: index,
+                })
+            }
+        };
+
+        let address = match mode {
+            Mode::Position => raw,
+            Mode::Immediate if written_param == Some(index) => {
+                return Err(IntcodeError::ImmediateWrite {
+                    ip,
+                    instruction,
+                    param: index,
+                })
+            }
+            Mode::Immediate => continue,
+            Mode::Relative => match M::Word::from_isize(machine.relative_base).checked_add(&raw) {
+                Some(address) => address,
+                None => {
+                    return Err(IntcodeError::RelativeBaseOverflow {
+                        ip,
+                        instruction,
+                        relative_base: machine.relative_base,
+                        offset: raw,
+                    })
+                }
+            },
+        };
+
+        check_address(ip, &instruction, address)?;
+    }
+
+    Ok(())
+}
+
+/// Check that an address used by the instruction at `ip` is valid. The
+/// highest address, `usize::MAX`, isn't: memory can't hold a cell there (see
+/// `MemoryError`).
+fn check_address<W: Word>(ip: usize, instruction: &W, address: W) -> Result<(), IntcodeError<W>> {
+    if address.is_negative() {
+        Err(IntcodeError::NegativeAddress {
+            ip,
+            instruction: instruction.clone(),
+            address,
+        })
+    } else if address
+        .to_address()
+        .and_then(|a| a.checked_add(1))
+        .is_none()
+    {
+        Err(IntcodeError::AddressTooLarge {
+            ip,
+            instruction: instruction.clone(),
+            address,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Create an operation that runs a single instruction of the machine, like
+/// `step`, but which returns an error instead of panicking if the instruction
+/// is invalid. The machine is left unchanged if an error is returned.
+pub fn try_step<M: Memory>(
+    input: impl IntoIterator<Item = M::Word>,
+) -> impl FnMut(&mut Machine<M>) -> StepResult<M::Word> {
+    let mut stepper = step(input);
+
+    move |machine| {
+        check_instruction(machine)?;
+        Ok(stepper(machine))
+    }
 }
 
 // Create an operation that runs a machine with the input until it blocks
 // on input, outputs a value, or halts
-pub fn run_until_block(
-    input: impl IntoIterator<Item = isize>,
-) -> impl FnMut(&mut Machine) -> MachineState {
+pub fn run_until_block<M: Memory>(
+    input: impl IntoIterator<Item = M::Word>,
+) -> impl FnMut(&mut Machine<M>) -> MachineState<M::Word> {
     let mut stepper = step(input);
 
     move |machine| loop {
@@ -101,22 +220,59 @@ pub fn run_until_block(
     }
 }
 
+/// Fallible version of `run_until_block`. Stops at the first invalid
+/// instruction, leaving the machine's IP pointing at it.
+pub fn try_run_until_block<M: Memory>(
+    input: impl IntoIterator<Item = M::Word>,
+) -> impl FnMut(&mut Machine<M>) -> RunResult<M::Word> {
+    let mut stepper = try_step(input);
+
+    move |machine| loop {
+        if let Some(state) = stepper(machine)? {
+            break Ok(state);
+        }
+    }
+}
+
 // Create an operation that feeds a single value into the input of the machine,
 // the runs it until it blocks. Note that there is no guarantee that the value
 // was actually read; it's possible for the machine to block without reading
-// anything. There's no way to detect this.
-pub fn feed(value: isize) -> impl FnMut(&mut Machine) -> MachineState {
+// anything. Use `feed_checked` to detect this.
+pub fn feed<M: Memory>(value: M::Word) -> impl FnMut(&mut Machine<M>) -> MachineState<M::Word> {
     run_until_block(Some(value))
 }
 
+/// Like `feed`, but also returns the fed value if the machine blocked
+/// without reading it.
+pub fn feed_checked<W: Word, M: Memory<Word = W>>(
+    value: W,
+) -> impl FnMut(&mut Machine<M>) -> (MachineState<W>, Option<W>) {
+    move |machine| {
+        let (state, mut unread) = run_with_input(machine, Some(value.clone()));
+        (state, unread.next())
+    }
+}
+
+/// Run a machine with the input until it blocks, like `run_until_block`.
+/// Returns the final state, along with the rest of the input which the
+/// machine didn't read.
+pub fn run_with_input<M: Memory, I: IntoIterator<Item = M::Word>>(
+    machine: &mut Machine<M>,
+    input: I,
+) -> (MachineState<M::Word>, I::IntoIter) {
+    let mut input = input.into_iter();
+    let state = run_until_block(input.by_ref())(machine);
+    (state, input)
+}
+
 // Convert a machine and an input into an iterator over the machine's outputs
 // until it halts. Panics if it blocks on input.
 // The machine is guaranteed to be in a HALT state after the interator finishes,
 // but we still take it by reference so that it can be reset for future runs.
-pub fn machine_iter<'a>(
-    input: impl IntoIterator<Item = isize> + 'a,
-    machine: &'a mut Machine,
-) -> impl Iterator<Item = isize> + 'a {
+pub fn machine_iter<'a, M: Memory + 'a>(
+    input: impl IntoIterator<Item = M::Word> + 'a,
+    machine: &'a mut Machine<M>,
+) -> impl Iterator<Item = M::Word> + 'a {
     let mut run_machine = run_until_block(input);
 
     iter::from_fn(move || match run_machine(machine) {
@@ -126,38 +282,38 @@ pub fn machine_iter<'a>(
     })
 }
 
-/// Run a machine in a background thread. The machine will read inputs and
-/// send outputs to the channels, blocking if necessary. It will gracefully
-/// stop if either channel is closed. It will close the output channel if
-/// it halts.
-pub fn threaded_machine(
-    mut machine: Machine,
-    input: channel::Receiver<isize>,
-    output: channel::Sender<isize>,
-) {
-    thread::spawn(move || {
-        let mut stepper = run_until_block(input);
-
-        loop {
-            match stepper(&mut machine) {
-                MachineState::NeedInput => break,
-                MachineState::Halt => break,
-                MachineState::Output(out) => match output.send(out) {
-                    Err(..) => break,
-                    Ok(()) => {}
-                },
-            }
-        }
-    });
-}
-
-pub fn make_threaded_machine(
-    machine: Machine,
-) -> (channel::Sender<isize>, channel::Receiver<isize>) {
-    let (send_input, recv_input) = channel::unbounded();
-    let (send_output, recv_output) = channel::unbounded();
+/// Fallible version of `machine_iter`. Instead of panicking, the iterator
+/// yields an error (and then ends) if the machine runs an invalid instruction
+/// or blocks on input.
+pub fn try_machine_iter<'a, M: Memory + 'a>(
+    input: impl IntoIterator<Item = M::Word> + 'a,
+    machine: &'a mut Machine<M>,
+) -> impl Iterator<Item = Result<M::Word, IntcodeError<M::Word>>> + 'a {
+    let mut run_machine = try_run_until_block(input);
+    let mut done = false;
 
-    threaded_machine(machine, recv_input, send_output);
+    iter::from_fn(move || {
+        if done {
+            return None;
+        }
 
-    (send_input, recv_output)
+        match run_machine(machine) {
+            Ok(MachineState::Output(value)) => Some(Ok(value)),
+            Ok(MachineState::Halt) => {
+                done = true;
+                None
+            }
+            Ok(MachineState::NeedInput) => {
+                done = true;
+                Some(Err(IntcodeError::InputExhausted {
+                    ip: IP.address(machine),
+                    instruction: IP.get(machine),
+                }))
+            }
+            Err(err) => {
+                done = true;
+                Some(Err(err))
+            }
+        }
+    })
 }
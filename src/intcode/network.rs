@@ -0,0 +1,338 @@
+//! A simulated network of intcode machines, as in day 23. Each machine is
+//! booted with its network address, then sends packets by outputting three
+//! values (destination address, X, Y) and receives them as two input values
+//! (X, Y), reading -1 when it has nothing to receive.
+//!
+//! The network is run one event at a time, with `next_event` or as an
+//! iterator. Packets are delivered in the order they were sent. When there
+//! are no packets in flight, the network runs an idle round, giving -1 to
+//! every machine; after enough consecutive idle rounds in which nothing was
+//! sent, the network is considered idle, and its NAT (if any) is given the
+//! chance to send a packet.
+
+use std::collections::{HashMap, VecDeque};
+use std::iter;
+
+use super::{run_until_block, Machine, MachineState};
+
+/// A packet sent between machines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packet {
+    /// The address of the machine that sent the packet. Packets sent by the
+    /// NAT have the NAT's address.
+    pub source: isize,
+    pub dest: isize,
+    pub x: isize,
+    pub y: isize,
+}
+
+/// Something that happened in the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkEvent {
+    /// A packet was delivered to a machine
+    Delivered(Packet),
+
+    /// A packet was sent to the NAT
+    NatReceived(Packet),
+
+    /// The network was idle, and the NAT sent a packet
+    NatSent(Packet),
+
+    /// A packet was sent to an address with no machine or NAT, or to a
+    /// machine which has halted. It is dropped.
+    Unrouted(Packet),
+
+    /// There were no packets in flight, so every machine was given an empty
+    /// input (-1)
+    IdleRound,
+
+    /// The machine at this address halted. It isn't run again, and packets
+    /// sent to it afterwards are reported as `Unrouted`.
+    Halted(isize),
+}
+
+/// A device that monitors the network, receiving packets sent to its address
+/// and sending packets when the network is idle.
+pub trait Nat {
+    /// The address packets are sent to to reach the NAT
+    fn address(&self) -> isize {
+        255
+    }
+
+    /// Called when a packet is sent to the NAT
+    fn receive(&mut self, packet: Packet);
+
+    /// Called when the network is idle. Returns a packet to send, if any.
+    /// The packet's source is set to the NAT's address.
+    fn idle(&mut self) -> Option<Packet>;
+}
+
+/// The NAT from day 23: when the network is idle, it sends the most recent
+/// packet it received to address 0. Each packet is only sent once.
+#[derive(Debug, Clone, Default)]
+pub struct LastPacketNat {
+    last: Option<Packet>,
+}
+
+impl LastPacketNat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Nat for LastPacketNat {
+    fn receive(&mut self, packet: Packet) {
+        self.last = Some(packet)
+    }
+
+    fn idle(&mut self) -> Option<Packet> {
+        self.last.take().map(|packet| Packet { dest: 0, ..packet })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    address: isize,
+    machine: Machine,
+    input: VecDeque<isize>,
+
+    /// Output values which haven't formed a complete packet yet
+    output: Vec<isize>,
+    halted: bool,
+}
+
+/// A network of machines. See the module documentation for details.
+pub struct Network {
+    nodes: Vec<Node>,
+    routes: HashMap<isize, usize>,
+    nat: Option<Box<dyn Nat>>,
+    in_flight: VecDeque<Packet>,
+    events: VecDeque<NetworkEvent>,
+    idle_threshold: usize,
+    quiet_rounds: usize,
+}
+
+impl Network {
+    /// Create a network of `n` copies of a program, with addresses 0 to n-1
+    pub fn new(program: &Machine, n: usize) -> Self {
+        Self::with_addresses(program, 0..n as isize)
+    }
+
+    /// Create a network with a copy of a program for each address. Panics if
+    /// an address is repeated.
+    pub fn with_addresses(program: &Machine, addresses: impl IntoIterator<Item = isize>) -> Self {
+        let mut network = Network {
+            nodes: Vec::new(),
+            routes: HashMap::new(),
+            nat: None,
+            in_flight: VecDeque::new(),
+            events: VecDeque::new(),
+            idle_threshold: 0,
+            quiet_rounds: 0,
+        };
+
+        for address in addresses {
+            if network
+                .routes
+                .insert(address, network.nodes.len())
+                .is_some()
+            {
+                panic!("Duplicate network address: {}", address);
+            }
+
+            network.nodes.push(Node {
+                address,
+                machine: program.clone(),
+                input: iter::once(address).collect(),
+                output: Vec::new(),
+                halted: false,
+            });
+        }
+
+        // Boot every machine
+        for index in 0..network.nodes.len() {
+            network.run_node(index);
+        }
+
+        network
+    }
+
+    /// Attach a NAT to the network. Packets sent to its address are given
+    /// to it rather than to a machine.
+    pub fn set_nat(&mut self, nat: impl Nat + 'static) {
+        self.nat = Some(Box::new(nat));
+    }
+
+    /// Set the number of consecutive idle rounds, in which no machine sent
+    /// anything, before the network is considered idle and the NAT is given
+    /// the chance to send. The default is 0: the network is idle as soon as
+    /// there are no packets in flight.
+    pub fn set_idle_threshold(&mut self, rounds: usize) {
+        self.idle_threshold = rounds;
+    }
+
+    /// The input queue of the machine at an address, if there is one
+    pub fn input_queue(&self, address: isize) -> Option<&VecDeque<isize>> {
+        self.routes
+            .get(&address)
+            .map(|&index| &self.nodes[index].input)
+    }
+
+    /// The machine at an address, if there is one
+    pub fn machine(&self, address: isize) -> Option<&Machine> {
+        self.routes
+            .get(&address)
+            .map(|&index| &self.nodes[index].machine)
+    }
+
+    /// Send a packet into the network, as though from outside it
+    pub fn send(&mut self, packet: Packet) {
+        self.route(packet)
+    }
+
+    /// Run the network until the next event. Returns None if every machine
+    /// has halted and there's nothing left to report.
+    pub fn next_event(&mut self) -> Option<NetworkEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+
+            if self.nodes.iter().all(|node| node.halted) {
+                return None;
+            }
+
+            if let Some(packet) = self.in_flight.pop_front() {
+                self.deliver(packet);
+            } else if self.quiet_rounds >= self.idle_threshold && self.nat_idle() {
+                self.quiet_rounds = 0;
+            } else {
+                self.idle_round();
+            }
+        }
+    }
+
+    /// Give the NAT a chance to send a packet. Returns true if it did.
+    fn nat_idle(&mut self) -> bool {
+        let packet = match self.nat.as_mut() {
+            None => return false,
+            Some(nat) => match nat.idle() {
+                None => return false,
+                Some(packet) => Packet {
+                    source: nat.address(),
+                    ..packet
+                },
+            },
+        };
+
+        self.events.push_back(NetworkEvent::NatSent(packet));
+        self.route(packet);
+        true
+    }
+
+    fn idle_round(&mut self) {
+        let mut sent = 0;
+
+        for index in 0..self.nodes.len() {
+            let node = &mut self.nodes[index];
+            if !node.halted && node.input.is_empty() {
+                node.input.push_back(-1);
+                sent += self.run_node(index);
+            }
+        }
+
+        if sent == 0 {
+            self.quiet_rounds += 1;
+        } else {
+            self.quiet_rounds = 0;
+        }
+
+        self.events.push_back(NetworkEvent::IdleRound);
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        let index = self.routes[&packet.dest];
+        let node = &mut self.nodes[index];
+
+        if node.halted {
+            self.events.push_back(NetworkEvent::Unrouted(packet));
+            return;
+        }
+
+        node.input.push_back(packet.x);
+        node.input.push_back(packet.y);
+        self.quiet_rounds = 0;
+        self.events.push_back(NetworkEvent::Delivered(packet));
+        self.run_node(index);
+    }
+
+    /// Send a packet to the NAT, put it in flight to a machine, or drop it
+    fn route(&mut self, packet: Packet) {
+        if let Some(nat) = self.nat.as_mut() {
+            if packet.dest == nat.address() {
+                nat.receive(packet);
+                self.events.push_back(NetworkEvent::NatReceived(packet));
+                return;
+            }
+        }
+
+        if self.routes.contains_key(&packet.dest) {
+            self.in_flight.push_back(packet);
+        } else {
+            self.events.push_back(NetworkEvent::Unrouted(packet));
+        }
+    }
+
+    /// Run a machine until it blocks, routing any packets it sends. Returns
+    /// the number of packets sent.
+    fn run_node(&mut self, index: usize) -> usize {
+        let mut sent = Vec::new();
+
+        let Node {
+            address,
+            machine,
+            input,
+            output,
+            halted,
+        } = &mut self.nodes[index];
+
+        let mut stepper = run_until_block(iter::from_fn(|| input.pop_front()));
+
+        loop {
+            match stepper(machine) {
+                MachineState::NeedInput => break,
+                MachineState::Halt => {
+                    *halted = true;
+                    self.events.push_back(NetworkEvent::Halted(*address));
+                    break;
+                }
+                MachineState::Output(value) => {
+                    output.push(value);
+                    if let [dest, x, y] = output[..] {
+                        output.clear();
+                        sent.push(Packet {
+                            source: *address,
+                            dest,
+                            x,
+                            y,
+                        });
+                    }
+                }
+            }
+        }
+
+        drop(stepper);
+
+        let count = sent.len();
+        sent.into_iter().for_each(|packet| self.route(packet));
+        count
+    }
+}
+
+impl Iterator for Network {
+    type Item = NetworkEvent;
+
+    fn next(&mut self) -> Option<NetworkEvent> {
+        self.next_event()
+    }
+}
@@ -0,0 +1,178 @@
+//! Step-level instrumentation for running machines. An `Observer` is attached
+//! to a run with `step_observed` or `run_observed`, and is told about each
+//! instruction and its effects as the machine runs. The plain `step` and
+//! `run_until_block` operations are unaffected, so unobserved runs cost
+//! nothing extra.
+
+use super::disasm::decode_at_ip;
+use super::{
+    address, check_instruction, param, step, Addressed, Instruction, Machine, MachineState, Memory,
+    Mode, Opcode, Operand, RunResult, StepResult, Value, IP,
+};
+
+/// Callbacks for the events in a machine run, on a machine whose memory holds
+/// `W` words. All methods default to doing nothing, so implementors only need
+/// to override the events they care about.
+pub trait Observer<W = isize> {
+    /// Called before each instruction is run. An input instruction that
+    /// blocks is reported again when it's retried.
+    fn instruction(&mut self, _ip: usize, _opcode: Opcode, _operands: &[Operand<W>]) {}
+
+    /// Called for each memory cell read as an instruction operand
+    fn read(&mut self, _address: usize, _value: W) {}
+
+    /// Called for each memory cell written by an instruction
+    fn write(&mut self, _address: usize, _old: W, _new: W) {}
+
+    /// Called when the relative base changes
+    fn relative_base(&mut self, _old: isize, _new: isize) {}
+
+    /// Called when an input value is consumed
+    fn input(&mut self, _value: W) {}
+
+    /// Called when an output value is produced
+    fn output(&mut self, _value: W) {}
+}
+
+impl<W, O: Observer<W> + ?Sized> Observer<W> for &mut O {
+    fn instruction(&mut self, ip: usize, opcode: Opcode, operands: &[Operand<W>]) {
+        (**self).instruction(ip, opcode, operands)
+    }
+
+    fn read(&mut self, address: usize, value: W) {
+        (**self).read(address, value)
+    }
+
+    fn write(&mut self, address: usize, old: W, new: W) {
+        (**self).write(address, old, new)
+    }
+
+    fn relative_base(&mut self, old: isize, new: isize) {
+        (**self).relative_base(old, new)
+    }
+
+    fn input(&mut self, value: W) {
+        (**self).input(value)
+    }
+
+    fn output(&mut self, value: W) {
+        (**self).output(value)
+    }
+}
+
+/// The null observer
+impl<W> Observer<W> for () {}
+
+/// A pair of observers, which are both told about each event, in order
+impl<W: Clone, A: Observer<W>, B: Observer<W>> Observer<W> for (A, B) {
+    fn instruction(&mut self, ip: usize, opcode: Opcode, operands: &[Operand<W>]) {
+        self.0.instruction(ip, opcode, operands);
+        self.1.instruction(ip, opcode, operands);
+    }
+
+    fn read(&mut self, address: usize, value: W) {
+        self.0.read(address, value.clone());
+        self.1.read(address, value);
+    }
+
+    fn write(&mut self, address: usize, old: W, new: W) {
+        self.0.write(address, old.clone(), new.clone());
+        self.1.write(address, old, new);
+    }
+
+    fn relative_base(&mut self, old: isize, new: isize) {
+        self.0.relative_base(old, new);
+        self.1.relative_base(old, new);
+    }
+
+    fn input(&mut self, value: W) {
+        self.0.input(value.clone());
+        self.1.input(value);
+    }
+
+    fn output(&mut self, value: W) {
+        self.0.output(value.clone());
+        self.1.output(value);
+    }
+}
+
+/// Create an operation that runs a single instruction of the machine, like
+/// `try_step`, reporting what it does to the observer. Pass `&mut observer`
+/// to keep access to the observer after the run.
+pub fn step_observed<M: Memory>(
+    input: impl IntoIterator<Item = M::Word>,
+    mut observer: impl Observer<M::Word>,
+) -> impl FnMut(&mut Machine<M>) -> StepResult<M::Word> {
+    let mut stepper = step(input);
+
+    move |machine| {
+        check_instruction(machine)?;
+
+        let (opcode, operands) = match decode_at_ip(machine) {
+            Some(Instruction::Op { opcode, operands }) => (opcode, operands),
+            // check_instruction guarantees that the instruction decodes
+            _ => unreachable!("Checked instruction failed to decode"),
+        };
+
+        observer.instruction(IP.address(machine), opcode, &operands);
+
+        // Report the reads, and find the destination of the write (if any)
+        // before running the instruction, since running it may change the
+        // parameters.
+        let mut destination = None;
+
+        for (index, operand) in (1..).zip(&operands) {
+            if operand.mode == Mode::Immediate {
+                continue;
+            }
+
+            let address = param(index).address(machine);
+
+            if opcode.written_param() == Some(index) {
+                destination = Some((address, param(index).get(machine)));
+            } else {
+                observer.read(address, param(index).get(machine));
+            }
+        }
+
+        let old_rb = machine.relative_base;
+        let state = stepper(machine);
+
+        if let Some((destination, old)) = destination {
+            // The input instruction doesn't write anything if it blocked
+            if state != Some(MachineState::NeedInput) {
+                let new: M::Word = address(destination).get(machine);
+                observer.write(destination, old, new.clone());
+
+                if opcode == Opcode::Input {
+                    observer.input(new);
+                }
+            }
+        }
+
+        if machine.relative_base != old_rb {
+            observer.relative_base(old_rb, machine.relative_base);
+        }
+
+        if let Some(MachineState::Output(ref value)) = state {
+            observer.output(value.clone());
+        }
+
+        Ok(state)
+    }
+}
+
+/// Create an operation that runs a machine until it blocks, like
+/// `try_run_until_block`, reporting each step to the observer.
+pub fn run_observed<M: Memory>(
+    input: impl IntoIterator<Item = M::Word>,
+    observer: impl Observer<M::Word>,
+) -> impl FnMut(&mut Machine<M>) -> RunResult<M::Word> {
+    let mut stepper = step_observed(input, observer);
+
+    move |machine| loop {
+        if let Some(state) = stepper(machine)? {
+            break Ok(state);
+        }
+    }
+}
@@ -1,18 +1,18 @@
 use std::convert::{TryFrom, TryInto};
 use std::fmt::{self, Debug, Formatter};
 
-use super::{opcode, AddUsize, Addressed, Machine, Value, IP};
+use super::{AddUsize, Addressed, Combinator, Machine, Memory, Value, Word, IP};
 
 #[must_use]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum MachineState {
-    Output(isize),
+pub enum MachineState<W = isize> {
+    Output(W),
     NeedInput,
     Halt,
 }
 
-impl MachineState {
-    pub fn expect_out(self, ctx: &'static str) -> isize {
+impl<W> MachineState<W> {
+    pub fn expect_out(self, ctx: &'static str) -> W {
         match self {
             MachineState::Output(value) => value,
             MachineState::Halt => panic!("Unexpected halt: {}", ctx),
@@ -21,39 +21,39 @@ impl MachineState {
     }
 }
 
-pub trait AsMachineState {
-    fn as_machine_state(&self) -> Option<MachineState>;
+pub trait AsMachineState<W = isize> {
+    fn into_machine_state(self) -> Option<MachineState<W>>;
 }
 
-impl AsMachineState for MachineState {
+impl<W> AsMachineState<W> for MachineState<W> {
     #[inline]
-    fn as_machine_state(&self) -> Option<MachineState> {
-        Some(*self)
+    fn into_machine_state(self) -> Option<MachineState<W>> {
+        Some(self)
     }
 }
 
-impl AsMachineState for Option<MachineState> {
+impl<W> AsMachineState<W> for Option<MachineState<W>> {
     #[inline]
-    fn as_machine_state(&self) -> Option<MachineState> {
-        *self
+    fn into_machine_state(self) -> Option<MachineState<W>> {
+        self
     }
 }
 
-impl AsMachineState for () {
+impl<W> AsMachineState<W> for () {
     #[inline]
-    fn as_machine_state(&self) -> Option<MachineState> {
+    fn into_machine_state(self) -> Option<MachineState<W>> {
         None
     }
 }
 
 // Create an operation that runs A, then B if A doesn't halt.
-pub fn chain<T: AsMachineState, U: AsMachineState>(
-    mut first: impl FnMut(&mut Machine) -> T,
-    mut second: impl FnMut(&mut Machine) -> U,
-) -> impl FnMut(&mut Machine) -> Option<MachineState> {
-    move |machine| match first(machine).as_machine_state() {
+pub fn chain<M: Memory, T: AsMachineState<M::Word>, U: AsMachineState<M::Word>>(
+    mut first: impl FnMut(&mut Machine<M>) -> T,
+    mut second: impl FnMut(&mut Machine<M>) -> U,
+) -> impl FnMut(&mut Machine<M>) -> Option<MachineState<M::Word>> {
+    move |machine| match first(machine).into_machine_state() {
         Some(state) => Some(state),
-        None => second(machine).as_machine_state(),
+        None => second(machine).into_machine_state(),
     }
 }
 
@@ -74,10 +74,10 @@ macro_rules! proc {
 
 /// Create an operation that fetches a value, then runs an operation, then
 /// blocks by Outputting the fetched value
-pub fn fetch_then<T: Value<Output = isize>>(
+pub fn fetch_then<M: Memory, T: Value<M::Word, Output = M::Word>>(
     value: T,
-    mut op: impl FnMut(&mut Machine),
-) -> impl FnMut(&mut Machine) -> MachineState {
+    mut op: impl FnMut(&mut Machine<M>),
+) -> impl FnMut(&mut Machine<M>) -> MachineState<M::Word> {
     move |machine| {
         let result = value.get(machine);
         op(machine);
@@ -86,46 +86,40 @@ pub fn fetch_then<T: Value<Output = isize>>(
 }
 
 /// Common implementation for set and set_external
-fn set_impl(
-    mut get_value: impl FnMut(&Machine) -> isize,
-    destination: impl Addressed,
-) -> impl FnMut(&mut Machine) {
+fn set_impl<M: Memory>(
+    mut get_value: impl FnMut(&Machine<M>) -> M::Word,
+    destination: impl Addressed<M::Word>,
+) -> impl FnMut(&mut Machine<M>) {
     move |machine| {
         let value = get_value(machine);
         let address = destination.address(machine);
-        if address >= machine.memory.len() {
-            // This correctly reserves ambitiously to prevent frequent
-            // allocations.
-            machine.memory.resize_with(address + 1, Default::default);
-        }
-
-        machine.memory[address] = value;
+        machine.memory.store(address, value);
     }
 }
 
 /// Create an operation that sets a memory location using an external function.
 /// The function is called each time the operation is executed. Primarily
 /// intended to support the intcode input operation.
-pub fn set_external(
-    mut operation: impl FnMut() -> isize,
-    destination: impl Addressed,
-) -> impl FnMut(&mut Machine) {
+pub fn set_external<M: Memory>(
+    mut operation: impl FnMut() -> M::Word,
+    destination: impl Addressed<M::Word>,
+) -> impl FnMut(&mut Machine<M>) {
     set_impl(move |_machine| operation(), destination)
 }
 
 /// Create an operation that sets a value using a value. This, along with
 /// set_ip, was the orignal intcode operation, and it serves as a template
 /// for the entire operational model.
-pub fn set(
-    value: impl Value<Output = isize>,
-    destination: impl Addressed,
-) -> impl FnMut(&mut Machine) {
+pub fn set<M: Memory>(
+    value: impl Value<M::Word, Output = M::Word>,
+    destination: impl Addressed<M::Word>,
+) -> impl FnMut(&mut Machine<M>) {
     set_impl(move |machine| value.get(machine), destination)
 }
 
 /// Create an operation that sets the instruction pointer to point to a given
 /// addressed value.
-pub fn set_ip(target: impl Addressed) -> impl Fn(&mut Machine) {
+pub fn set_ip<M: Memory>(target: impl Addressed<M::Word>) -> impl Fn(&mut Machine<M>) {
     move |machine| {
         machine.instruction_pointer = target.address(machine);
     }
@@ -133,28 +127,43 @@ pub fn set_ip(target: impl Addressed) -> impl Fn(&mut Machine) {
 
 /// Create an operation that increases the instruction pointer by the given
 /// amount
-pub fn advance_ip(offset: usize) -> impl Fn(&mut Machine) {
+pub fn advance_ip<M: Memory>(offset: usize) -> impl Fn(&mut Machine<M>) {
     set_ip(IP.offset(offset))
 }
 
-/// Create an operation that offsets the relative base by a given value
-pub fn move_rb(offset: impl Value<Output = isize>) -> impl Fn(&mut Machine) {
+/// Create an operation that offsets the relative base by a given value.
+/// Panics if the value doesn't fit in an `isize`, or if the new relative base
+/// overflows.
+pub fn move_rb<M: Memory>(
+    offset: impl Value<M::Word, Output = M::Word>,
+) -> impl Fn(&mut Machine<M>) {
     move |machine| {
-        machine.relative_base += offset.get(machine);
+        let offset = offset.get(machine);
+        let rb_offset = offset
+            .to_isize()
+            .unwrap_or_else(|| panic!("Invalid relative base offset: {}", offset));
+
+        machine.relative_base = machine
+            .relative_base
+            .checked_add(rb_offset)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Relative base overflow: {} + {}",
+                    machine.relative_base, rb_offset
+                )
+            });
     }
 }
 
 /// Create an operation that runs the inner operation only if the opcode
 /// code is a certain value
-pub fn match_opcode<T: AsMachineState>(
+pub fn match_opcode<M: Memory, T: AsMachineState<M::Word>>(
     code: isize,
-    mut op: impl FnMut(&mut Machine) -> T,
-) -> impl FnMut(&mut Machine) -> Option<MachineState> {
-    let opcode = IP.map(opcode);
-
+    mut op: impl FnMut(&mut Machine<M>) -> T,
+) -> impl FnMut(&mut Machine<M>) -> Option<MachineState<M::Word>> {
     move |machine| {
-        if opcode.get(machine) == code {
-            op(machine).as_machine_state()
+        if machine.opcode() == Some(code) {
+            op(machine).into_machine_state()
         } else {
             None
         }
@@ -0,0 +1,320 @@
+//! Execution profiling. A `Profiler` is an `Observer` which counts the
+//! instructions run at each address and of each opcode, and the reads and
+//! writes of each memory cell:
+//!
+//! ```ignore
+//! let mut profiler = Profiler::new();
+//! let state = run_observed(input, &mut profiler)(&mut machine)?;
+//!
+//! println!("{}", profiler.report(10));
+//! profiler.write_csv(File::create("profile.csv")?)?;
+//! ```
+//!
+//! Reads and writes are those made by instructions through their operands;
+//! fetching the instruction itself (and its immediate operands) isn't
+//! counted as a read. An input instruction which blocks isn't counted until
+//! it actually reads its input.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+
+use super::{Instruction, Observer, Opcode, Operand, Word, INSTRUCTION_SET};
+
+#[derive(Debug, Clone, Default)]
+struct CellProfile<W> {
+    executed: u64,
+    reads: u64,
+    writes: u64,
+
+    /// The instruction most recently run at this address
+    instruction: Option<Instruction<W>>,
+}
+
+/// An `Observer` which profiles a machine run. See the module documentation
+/// for details.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler<W = isize> {
+    cells: HashMap<usize, CellProfile<W>>,
+    opcodes: [u64; INSTRUCTION_SET.len()],
+    total: u64,
+
+    /// The address of an input instruction which hasn't read its input yet
+    pending_input: Option<usize>,
+}
+
+impl<W: Word> Profiler<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discard everything recorded so far
+    pub fn clear(&mut self) {
+        *self = Self::default()
+    }
+
+    /// The total number of instructions run
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The number of instructions run with this opcode
+    pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+        self.opcodes[opcode as usize]
+    }
+
+    /// The number of instructions run at this address
+    pub fn executions(&self, address: usize) -> u64 {
+        self.cells.get(&address).map_or(0, |cell| cell.executed)
+    }
+
+    /// The number of times this cell was read as an operand
+    pub fn reads(&self, address: usize) -> u64 {
+        self.cells.get(&address).map_or(0, |cell| cell.reads)
+    }
+
+    /// The number of times this cell was written by an instruction
+    pub fn writes(&self, address: usize) -> u64 {
+        self.cells.get(&address).map_or(0, |cell| cell.writes)
+    }
+
+    fn count(&mut self, address: usize) {
+        let opcode = match self.cells.get_mut(&address) {
+            Some(CellProfile {
+                executed,
+                instruction: Some(Instruction::Op { opcode, .. }),
+                ..
+            }) => {
+                *executed += 1;
+                *opcode
+            }
+            // The instruction is always recorded before it's counted
+            _ => unreachable!("Counted an instruction that wasn't recorded"),
+        };
+
+        self.opcodes[opcode as usize] += 1;
+        self.total += 1;
+    }
+
+    /// The `n` addresses where the most instructions were run, most first
+    pub fn hot_spots(&self, n: usize) -> Vec<HotSpot<W>> {
+        let mut spots: Vec<HotSpot<W>> = self
+            .cells
+            .iter()
+            .filter_map(|(&address, cell)| match cell.instruction {
+                Some(ref instruction) if cell.executed > 0 => Some(HotSpot {
+                    address,
+                    count: cell.executed,
+                    instruction: instruction.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        spots.sort_by(|a, b| b.count.cmp(&a.count).then(a.address.cmp(&b.address)));
+        spots.truncate(n);
+        spots
+    }
+
+    /// The `n` cells which were read or written the most, most first
+    pub fn hot_cells(&self, n: usize) -> Vec<CellAccess> {
+        let mut cells: Vec<CellAccess> = self
+            .cells
+            .iter()
+            .filter(|(_, cell)| cell.reads + cell.writes > 0)
+            .map(|(&address, cell)| CellAccess {
+                address,
+                reads: cell.reads,
+                writes: cell.writes,
+            })
+            .collect();
+
+        cells.sort_by(|a, b| {
+            (b.reads + b.writes)
+                .cmp(&(a.reads + a.writes))
+                .then(a.address.cmp(&b.address))
+        });
+        cells.truncate(n);
+        cells
+    }
+
+    /// Summarize the profile, with the top `n` hot spots and cells
+    pub fn report(&self, n: usize) -> ProfileReport<W> {
+        ProfileReport {
+            total: self.total,
+            opcodes: INSTRUCTION_SET
+                .iter()
+                .map(|spec| (spec.opcode, self.opcode_count(spec.opcode)))
+                .filter(|&(_, count)| count > 0)
+                .collect(),
+            hot_spots: self.hot_spots(n),
+            hot_cells: self.hot_cells(n),
+        }
+    }
+
+    /// Write the complete profile as CSV, with a row for each address that
+    /// was run, read, or written, in address order. The columns are
+    /// `address,executed,reads,writes,instruction`.
+    pub fn write_csv(&self, mut dest: impl Write) -> io::Result<()> {
+        let mut addresses: Vec<usize> = self.cells.keys().copied().collect();
+        addresses.sort();
+
+        writeln!(dest, "address,executed,reads,writes,instruction")?;
+
+        for address in addresses {
+            let cell = &self.cells[&address];
+            write!(
+                dest,
+                "{},{},{},{},",
+                address, cell.executed, cell.reads, cell.writes
+            )?;
+
+            match cell.instruction {
+                // Operands are separated by commas, so the instruction is
+                // always quoted
+                Some(ref instruction) if cell.executed > 0 => {
+                    writeln!(dest, "\"{}\"", instruction)?
+                }
+                _ => writeln!(dest)?,
+            }
+        }
+
+        dest.flush()
+    }
+
+    /// Write the instruction counts for each opcode as CSV. The columns are
+    /// `opcode,executed`; every opcode is included, even if it never ran.
+    pub fn write_opcode_csv(&self, mut dest: impl Write) -> io::Result<()> {
+        writeln!(dest, "opcode,executed")?;
+
+        for spec in INSTRUCTION_SET.iter() {
+            writeln!(dest, "{},{}", spec.mnemonic, self.opcode_count(spec.opcode))?;
+        }
+
+        dest.flush()
+    }
+}
+
+impl<W: Word> Observer<W> for Profiler<W> {
+    fn instruction(&mut self, ip: usize, opcode: Opcode, operands: &[Operand<W>]) {
+        let cell = self.cells.entry(ip).or_default();
+
+        // Only build a new instruction if the code here has changed
+        match cell.instruction {
+            Some(Instruction::Op {
+                opcode: old_opcode,
+                operands: ref old_operands,
+            }) if old_opcode == opcode && old_operands[..] == *operands => {}
+            _ => {
+                cell.instruction = Some(Instruction::Op {
+                    opcode,
+                    operands: operands.to_vec(),
+                })
+            }
+        }
+
+        // Input instructions might block; they're counted when they read
+        if opcode == Opcode::Input {
+            self.pending_input = Some(ip);
+        } else {
+            self.count(ip);
+        }
+    }
+
+    fn read(&mut self, address: usize, _value: W) {
+        self.cells.entry(address).or_default().reads += 1;
+    }
+
+    fn write(&mut self, address: usize, _old: W, _new: W) {
+        self.cells.entry(address).or_default().writes += 1;
+    }
+
+    fn input(&mut self, _value: W) {
+        if let Some(ip) = self.pending_input.take() {
+            self.count(ip);
+        }
+    }
+}
+
+/// An address where instructions were run, and how many
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotSpot<W = isize> {
+    pub address: usize,
+    pub count: u64,
+
+    /// The instruction most recently run at this address
+    pub instruction: Instruction<W>,
+}
+
+/// A memory cell, and how many times it was read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellAccess {
+    pub address: usize,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// A summary of a `Profiler`, printable as text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport<W = isize> {
+    pub total: u64,
+
+    /// The opcodes which were run, in instruction set order
+    pub opcodes: Vec<(Opcode, u64)>,
+    pub hot_spots: Vec<HotSpot<W>>,
+    pub hot_cells: Vec<CellAccess>,
+}
+
+impl<W> ProfileReport<W> {
+    fn percent(&self, count: u64) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+impl<W: Word> Display for ProfileReport<W> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{} instructions run", self.total)?;
+
+        writeln!(f, "\nopcode  {:>12}  {:>7}", "count", "percent")?;
+        for &(opcode, count) in &self.opcodes {
+            writeln!(
+                f,
+                "{:<6}  {:>12}  {:>6.2}%",
+                opcode.mnemonic(),
+                count,
+                self.percent(count)
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\n{:>7}  {:>12}  {:>7}  instruction",
+            "address", "count", "percent"
+        )?;
+        for spot in &self.hot_spots {
+            writeln!(
+                f,
+                "{:>7}  {:>12}  {:>6.2}%  {}",
+                spot.address,
+                spot.count,
+                self.percent(spot.count),
+                spot.instruction
+            )?;
+        }
+
+        write!(f, "\n{:>7}  {:>12}  {:>12}", "cell", "reads", "writes")?;
+        for cell in &self.hot_cells {
+            write!(
+                f,
+                "\n{:>7}  {:>12}  {:>12}",
+                cell.address, cell.reads, cell.writes
+            )?;
+        }
+
+        Ok(())
+    }
+}
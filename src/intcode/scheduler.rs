@@ -0,0 +1,327 @@
+//! A deterministic, single-threaded scheduler for running many machines
+//! together. The scheduler owns its machines and their input queues, and
+//! repeatedly picks a runnable machine according to its `Policy`, running it
+//! for one slice: until it outputs, blocks on input, or halts. Outputs can be
+//! connected to the input of another machine. Every slice is recorded, so a
+//! run can be inspected afterwards; given the same machines and input, the
+//! schedule is always the same.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+
+use super::{step, Machine, MachineState};
+
+/// How the scheduler picks the next machine to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Cycle through the machines in order, starting after the one that ran
+    /// most recently
+    RoundRobin,
+
+    /// Always run the runnable machine with the lowest ID
+    LowestFirst,
+}
+
+/// A record of a single slice of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slice {
+    /// The ID of the machine that ran
+    pub machine: usize,
+
+    /// The number of instructions it ran
+    pub instructions: usize,
+
+    /// The state it stopped in
+    pub state: MachineState,
+}
+
+impl Display for Slice {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "machine {}: {} instructions, {:?}",
+            self.machine, self.instructions, self.state
+        )
+    }
+}
+
+/// Why a machine couldn't run any further
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blocked {
+    /// The machine needs input, and no machine is connected to its input
+    NoInputSource,
+
+    /// The machine needs input, and every machine connected to its input
+    /// has halted
+    SourcesHalted(Vec<usize>),
+
+    /// The machine needs input from these machines, which are themselves
+    /// blocked
+    Deadlock(Vec<usize>),
+}
+
+/// The final state of a machine at the end of a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Halted,
+    Blocked(Blocked),
+}
+
+/// The result of a complete run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The final state of each machine, by ID
+    pub outcomes: Vec<Outcome>,
+
+    /// The number of slices run
+    pub slices: usize,
+}
+
+impl Report {
+    /// The IDs of the machines that halted
+    pub fn halted(&self) -> impl Iterator<Item = usize> + '_ {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .filter(|(_, outcome)| **outcome == Outcome::Halted)
+            .map(|(id, _)| id)
+    }
+
+    /// The IDs of the machines that are blocked, and why
+    pub fn blocked(&self) -> impl Iterator<Item = (usize, &Blocked)> + '_ {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .filter_map(|(id, outcome)| match outcome {
+                Outcome::Halted => None,
+                Outcome::Blocked(reason) => Some((id, reason)),
+            })
+    }
+
+    /// True if every machine halted
+    pub fn all_halted(&self) -> bool {
+        self.blocked().next().is_none()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{} slices", self.slices)?;
+
+        for (id, outcome) in self.outcomes.iter().enumerate() {
+            write!(f, "machine {}: ", id)?;
+            match outcome {
+                Outcome::Halted => writeln!(f, "halted")?,
+                Outcome::Blocked(Blocked::NoInputSource) => {
+                    writeln!(f, "blocked on input with no source")?
+                }
+                Outcome::Blocked(Blocked::SourcesHalted(sources)) => {
+                    writeln!(f, "blocked on input from halted machines {:?}", sources)?
+                }
+                Outcome::Blocked(Blocked::Deadlock(sources)) => {
+                    writeln!(f, "deadlocked on input from machines {:?}", sources)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Process {
+    machine: Machine,
+    input: VecDeque<isize>,
+
+    /// Outputs not sent to another machine
+    output: Vec<isize>,
+    last_output: Option<isize>,
+    target: Option<usize>,
+
+    /// The state from this machine's most recent slice
+    state: Option<MachineState>,
+}
+
+impl Process {
+    fn runnable(&self) -> bool {
+        match self.state {
+            Some(MachineState::Halt) => false,
+            Some(MachineState::NeedInput) => !self.input.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+/// A scheduler for a group of machines. See the module documentation for
+/// details.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    processes: Vec<Process>,
+    policy: Policy,
+    schedule: Vec<Slice>,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy) -> Self {
+        Scheduler {
+            processes: Vec::new(),
+            policy,
+            schedule: Vec::new(),
+        }
+    }
+
+    /// Add a machine to the scheduler. Returns its ID; IDs are assigned in
+    /// order, starting at 0.
+    pub fn add(&mut self, machine: Machine) -> usize {
+        self.processes.push(Process {
+            machine,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            last_output: None,
+            target: None,
+            state: None,
+        });
+
+        self.processes.len() - 1
+    }
+
+    /// Add values to the end of a machine's input queue
+    pub fn push_input(&mut self, id: usize, values: impl IntoIterator<Item = isize>) {
+        self.processes[id].input.extend(values)
+    }
+
+    /// Send all future outputs of the `from` machine to the input of the
+    /// `to` machine.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.processes.len(), "No machine with ID {}", to);
+        self.processes[from].target = Some(to);
+    }
+
+    pub fn machine(&self, id: usize) -> &Machine {
+        &self.processes[id].machine
+    }
+
+    /// The input values a machine hasn't read yet
+    pub fn pending_input(&self, id: usize) -> &VecDeque<isize> {
+        &self.processes[id].input
+    }
+
+    /// The outputs of a machine which weren't sent to another machine
+    pub fn output(&self, id: usize) -> &[isize] {
+        &self.processes[id].output
+    }
+
+    /// The most recent output of a machine, including outputs sent to
+    /// another machine
+    pub fn last_output(&self, id: usize) -> Option<isize> {
+        self.processes[id].last_output
+    }
+
+    /// Every slice run so far, in order
+    pub fn schedule(&self) -> &[Slice] {
+        &self.schedule
+    }
+
+    /// Pick the next machine to run, if any are runnable
+    fn pick(&self) -> Option<usize> {
+        let count = self.processes.len();
+        let start = match (self.policy, self.schedule.last()) {
+            (Policy::RoundRobin, Some(slice)) => slice.machine + 1,
+            _ => 0,
+        };
+
+        (start..start + count)
+            .map(|id| id % count)
+            .find(|&id| self.processes[id].runnable())
+    }
+
+    /// Run a single slice of the next runnable machine. Returns None if no
+    /// machine is runnable.
+    pub fn run_slice(&mut self) -> Option<Slice> {
+        let id = self.pick()?;
+
+        let Process {
+            machine,
+            input,
+            state,
+            ..
+        } = &mut self.processes[id];
+
+        let mut stepper = step(iter::from_fn(|| input.pop_front()));
+        let mut instructions = 0;
+
+        let result = loop {
+            match stepper(machine) {
+                None => instructions += 1,
+                Some(MachineState::Output(value)) => {
+                    instructions += 1;
+                    break MachineState::Output(value);
+                }
+                Some(result) => break result,
+            }
+        };
+
+        drop(stepper);
+        *state = Some(result);
+
+        if let MachineState::Output(value) = result {
+            let process = &mut self.processes[id];
+            process.last_output = Some(value);
+
+            match process.target {
+                Some(target) => self.processes[target].input.push_back(value),
+                None => process.output.push(value),
+            }
+        }
+
+        let slice = Slice {
+            machine: id,
+            instructions,
+            state: result,
+        };
+
+        self.schedule.push(slice);
+        Some(slice)
+    }
+
+    /// Run machines until none of them can run any further, then report
+    /// their final states.
+    pub fn run(&mut self) -> Report {
+        let start = self.schedule.len();
+        while self.run_slice().is_some() {}
+
+        Report {
+            outcomes: (0..self.processes.len())
+                .map(|id| self.outcome(id))
+                .collect(),
+            slices: self.schedule.len() - start,
+        }
+    }
+
+    /// Determine why a machine that isn't runnable has stopped
+    fn outcome(&self, id: usize) -> Outcome {
+        if self.processes[id].state == Some(MachineState::Halt) {
+            return Outcome::Halted;
+        }
+
+        let sources: Vec<usize> = self
+            .processes
+            .iter()
+            .enumerate()
+            .filter(|(_, process)| process.target == Some(id))
+            .map(|(source, _)| source)
+            .collect();
+
+        Outcome::Blocked(if sources.is_empty() {
+            Blocked::NoInputSource
+        } else if sources
+            .iter()
+            .all(|&source| self.processes[source].state == Some(MachineState::Halt))
+        {
+            Blocked::SourcesHalted(sources)
+        } else {
+            Blocked::Deadlock(sources)
+        })
+    }
+}
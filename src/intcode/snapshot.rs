@@ -0,0 +1,191 @@
+//! Saving and restoring complete machine state. The on-disk format is a
+//! little-endian binary layout:
+//!
+//! ```text
+//! magic            4 bytes   b"INTC"
+//! version          u32       currently 2
+//! ip               u64
+//! relative base    i64
+//! overflow policy  u8        0 wrapping, 1 checked, 2 saturating
+//! memory           u64 length, followed by that many i64 cells
+//! pending input    u64 length, followed by that many i64 values
+//! pending output   u64 length, followed by that many i64 values
+//! ```
+//!
+//! Version 1 snapshots, which have no overflow policy, can still be loaded;
+//! their machines use the default policy.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use super::error::SnapshotError;
+use super::{Debugger, Machine, OverflowPolicy};
+
+const MAGIC: &[u8; 4] = b"INTC";
+const VERSION: u32 = 2;
+
+/// A machine, plus any input and output buffered around it
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub machine: Machine,
+    pub input: Vec<isize>,
+    pub output: Vec<isize>,
+}
+
+fn write_u64(dest: &mut impl Write, value: u64) -> io::Result<()> {
+    dest.write_all(&value.to_le_bytes())
+}
+
+fn write_values(dest: &mut impl Write, values: &[isize]) -> io::Result<()> {
+    write_u64(dest, values.len() as u64)?;
+    values
+        .iter()
+        .try_for_each(|&value| dest.write_all(&(value as i64).to_le_bytes()))
+}
+
+fn read_bytes<'a>(source: &mut impl Read, buf: &'a mut [u8]) -> Result<&'a [u8], SnapshotError> {
+    source.read_exact(buf).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => SnapshotError::Truncated,
+        _ => SnapshotError::Io(err),
+    })?;
+    Ok(buf)
+}
+
+fn read_u8(source: &mut impl Read) -> Result<u8, SnapshotError> {
+    let mut buf = [0; 1];
+    read_bytes(source, &mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(source: &mut impl Read) -> Result<u32, SnapshotError> {
+    let mut buf = [0; 4];
+    read_bytes(source, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(source: &mut impl Read) -> Result<u64, SnapshotError> {
+    let mut buf = [0; 8];
+    read_bytes(source, &mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_isize(source: &mut impl Read) -> Result<isize, SnapshotError> {
+    let mut buf = [0; 8];
+    read_bytes(source, &mut buf)?;
+    let value = i64::from_le_bytes(buf);
+    isize::try_from(value).map_err(|_| SnapshotError::OutOfRange)
+}
+
+fn read_usize(source: &mut impl Read) -> Result<usize, SnapshotError> {
+    let value = read_u64(source)?;
+    usize::try_from(value).map_err(|_| SnapshotError::OutOfRange)
+}
+
+fn read_values(source: &mut impl Read) -> Result<Vec<isize>, SnapshotError> {
+    let len = read_usize(source)?;
+
+    // Don't trust the length for the allocation, in case the file is corrupt;
+    // a truncated file should be an error, not an out-of-memory abort.
+    let mut values = Vec::with_capacity(len.min(1 << 16));
+    for _ in 0..len {
+        values.push(read_isize(source)?);
+    }
+    Ok(values)
+}
+
+fn write_snapshot(
+    mut dest: impl Write,
+    machine: &Machine,
+    input: &[isize],
+    output: &[isize],
+) -> io::Result<()> {
+    dest.write_all(MAGIC)?;
+    dest.write_all(&VERSION.to_le_bytes())?;
+    write_u64(&mut dest, machine.instruction_pointer as u64)?;
+    dest.write_all(&(machine.relative_base as i64).to_le_bytes())?;
+    dest.write_all(&[match machine.overflow {
+        OverflowPolicy::Wrapping => 0,
+        OverflowPolicy::Checked => 1,
+        OverflowPolicy::Saturating => 2,
+    }])?;
+    write_values(&mut dest, &machine.memory)?;
+    write_values(&mut dest, input)?;
+    write_values(&mut dest, output)?;
+    dest.flush()
+}
+
+impl Snapshot {
+    /// Write this snapshot to a writer
+    pub fn save_to(&self, dest: impl Write) -> io::Result<()> {
+        write_snapshot(dest, &self.machine, &self.input, &self.output)
+    }
+
+    /// Read a snapshot from a reader
+    pub fn load_from(mut source: impl Read) -> Result<Self, SnapshotError> {
+        let mut magic = [0; 4];
+        if read_bytes(&mut source, &mut magic)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+
+        let version = read_u32(&mut source)?;
+        if version != 1 && version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let instruction_pointer = read_usize(&mut source)?;
+        let relative_base = read_isize(&mut source)?;
+
+        let overflow = match version {
+            1 => OverflowPolicy::default(),
+            _ => match read_u8(&mut source)? {
+                0 => OverflowPolicy::Wrapping,
+                1 => OverflowPolicy::Checked,
+                2 => OverflowPolicy::Saturating,
+                _ => return Err(SnapshotError::OutOfRange),
+            },
+        };
+
+        let memory = read_values(&mut source)?;
+
+        Ok(Snapshot {
+            machine: Machine {
+                instruction_pointer,
+                relative_base,
+                memory,
+                overflow,
+            },
+            input: read_values(&mut source)?,
+            output: read_values(&mut source)?,
+        })
+    }
+}
+
+impl Machine {
+    /// Save this machine's complete state to a writer
+    pub fn save_to(&self, dest: impl Write) -> io::Result<()> {
+        write_snapshot(dest, self, &[], &[])
+    }
+
+    /// Load a machine saved with `save_to`. Any buffered input or output in
+    /// the snapshot is discarded; use `Snapshot::load_from` to retrieve it.
+    pub fn load_from(source: impl Read) -> Result<Self, SnapshotError> {
+        Snapshot::load_from(source).map(|snapshot| snapshot.machine)
+    }
+}
+
+impl Debugger {
+    /// Save the machine's complete state, along with the pending input queue.
+    /// Breakpoints and watchpoints are not saved.
+    pub fn save_to(&self, dest: impl Write) -> io::Result<()> {
+        let input: Vec<isize> = self.pending_input().iter().copied().collect();
+        write_snapshot(dest, self.machine(), &input, &[])
+    }
+
+    /// Load a debugger from a snapshot, restoring its pending input queue
+    pub fn load_from(source: impl Read) -> Result<Self, SnapshotError> {
+        let snapshot = Snapshot::load_from(source)?;
+        let mut debugger = Debugger::new(snapshot.machine);
+        debugger.push_input(snapshot.input);
+        Ok(debugger)
+    }
+}
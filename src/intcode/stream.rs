@@ -0,0 +1,132 @@
+//! An async interface for machines. A `MachineStream` reads its input from
+//! an async `Stream`, and is itself a `Stream` of the machine's outputs. It
+//! runs the machine on whatever task polls it, yielding whenever the machine
+//! needs input that isn't available yet, so many machines can share a single
+//! thread. See the `executor` module for a minimal executor to drive them.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::stream::{FusedStream, Stream};
+
+use super::{run_with_input, Machine, MachineState, Memory};
+
+/// A machine driven by an async input stream. Its outputs are available as
+/// a `Stream`, which ends when the machine halts, or when it needs input
+/// after the input stream has ended.
+///
+/// Since a `MachineStream` is a stream of values, the output of one machine
+/// can be used directly as the input of another.
+#[derive(Debug)]
+pub struct MachineStream<I, M = Vec<isize>> {
+    machine: Machine<M>,
+    input: I,
+
+    /// An input value which has been received but not yet read by the
+    /// machine
+    pending: Option<isize>,
+    done: bool,
+}
+
+impl<I, M: Memory<Word = isize>> MachineStream<I, M> {
+    pub fn new(machine: Machine<M>, input: I) -> Self {
+        MachineStream {
+            machine,
+            input,
+            pending: None,
+            done: false,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<M> {
+        &self.machine
+    }
+
+    /// Get the machine and input stream back. Returns any input value which
+    /// was taken from the stream but not yet read by the machine.
+    pub fn into_inner(self) -> (Machine<M>, I, Option<isize>) {
+        (self.machine, self.input, self.pending)
+    }
+}
+
+impl<I, M> Stream for MachineStream<I, M>
+where
+    I: Stream<Item = isize> + Unpin,
+    M: Memory<Word = isize> + Unpin,
+{
+    type Item = isize;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<isize>> {
+        let this = self.get_mut();
+
+        while !this.done {
+            let (state, mut unread) = run_with_input(&mut this.machine, this.pending.take());
+            this.pending = unread.next();
+
+            match state {
+                MachineState::Output(value) => return Poll::Ready(Some(value)),
+                MachineState::NeedInput => match Pin::new(&mut this.input).poll_next(ctx) {
+                    Poll::Ready(Some(value)) => this.pending = Some(value),
+                    Poll::Ready(None) => this.done = true,
+                    Poll::Pending => return Poll::Pending,
+                },
+                MachineState::Halt => this.done = true,
+            }
+        }
+
+        Poll::Ready(None)
+    }
+}
+
+impl<I, M> FusedStream for MachineStream<I, M>
+where
+    I: Stream<Item = isize> + Unpin,
+    M: Memory<Word = isize> + Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// A stream of the values from an iterator, which are always ready. Create
+/// one with `stream_iter`.
+#[derive(Debug, Clone)]
+pub struct IterStream<I> {
+    iter: I,
+}
+
+impl<I: Iterator + Unpin> Stream for IterStream<I> {
+    type Item = I::Item;
+
+    fn poll_next(self: Pin<&mut Self>, _ctx: &mut Context) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.get_mut().iter.next())
+    }
+}
+
+/// Create a stream from an iterator, for use as a machine's input
+pub fn stream_iter<I: IntoIterator>(iter: I) -> IterStream<I::IntoIter> {
+    IterStream {
+        iter: iter.into_iter(),
+    }
+}
+
+/// A future which resolves to the next item in a stream. Create one with
+/// `next`.
+#[derive(Debug)]
+pub struct Next<'a, S> {
+    stream: &'a mut S,
+}
+
+impl<S: Stream + Unpin> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<S::Item>> {
+        Pin::new(&mut *self.get_mut().stream).poll_next(ctx)
+    }
+}
+
+/// Get the next item from a stream
+pub fn next<S: Stream + Unpin>(stream: &mut S) -> Next<'_, S> {
+    Next { stream }
+}
@@ -0,0 +1,247 @@
+//! Running machines in background threads. Each machine reads its input
+//! from a channel and sends its output to another, blocking as necessary.
+//! The thread returns a `MachineHandle`, which can be joined to get back the
+//! machine and the reason it stopped.
+
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{self, Receiver, Sender};
+use crossbeam::select;
+
+use super::{try_step, IntcodeError, Machine, MachineState, Memory};
+
+/// The number of instructions run between checks for cancellation or
+/// timeout, while the machine isn't blocked on a channel.
+const CHECK_INTERVAL: usize = 1024;
+
+/// The reason a threaded machine stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The machine halted
+    Halted,
+
+    /// The machine needed input, and the input channel was closed
+    InputClosed,
+
+    /// The machine produced output, and the output channel was closed. The
+    /// output value is lost.
+    OutputClosed,
+
+    /// The machine was cancelled with `MachineHandle::cancel`
+    Cancelled,
+
+    /// The machine ran for longer than its timeout
+    TimedOut,
+
+    /// The machine tried to run an invalid instruction
+    Fault(IntcodeError),
+}
+
+impl Display for ExitReason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ExitReason::Halted => f.write_str("halted"),
+            ExitReason::InputClosed => f.write_str("input channel closed"),
+            ExitReason::OutputClosed => f.write_str("output channel closed"),
+            ExitReason::Cancelled => f.write_str("cancelled"),
+            ExitReason::TimedOut => f.write_str("timed out"),
+            ExitReason::Fault(err) => write!(f, "fault: {}", err),
+        }
+    }
+}
+
+/// Options for `spawn_machine`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThreadOptions {
+    /// The capacity of the input channel, or None for an unbounded channel
+    pub input_capacity: Option<usize>,
+
+    /// The capacity of the output channel, or None for an unbounded channel.
+    /// When the channel is full, the machine blocks until there is room.
+    pub output_capacity: Option<usize>,
+
+    /// Stop the machine with `ExitReason::TimedOut` if it runs for longer
+    /// than this, including time spent blocked on the channels
+    pub timeout: Option<Duration>,
+}
+
+/// A handle to a machine running in a background thread. Dropping the handle
+/// detaches the thread; the machine keeps running.
+#[derive(Debug)]
+pub struct MachineHandle<M = Vec<isize>> {
+    thread: JoinHandle<(Machine<M>, ExitReason)>,
+    cancel: Sender<()>,
+}
+
+impl<M> MachineHandle<M> {
+    /// Ask the machine to stop. It will stop the next time it checks, which
+    /// is immediately if it's blocked on a channel. The machine will exit
+    /// with `ExitReason::Cancelled`, unless it stops for some other reason
+    /// first.
+    pub fn cancel(&self) {
+        // If this fails, the machine has already stopped
+        let _ = self.cancel.try_send(());
+    }
+
+    /// Wait for the machine to stop, and get back the machine and the reason
+    /// it stopped. Returns an error if the thread panicked.
+    pub fn join(self) -> thread::Result<(Machine<M>, ExitReason)> {
+        self.thread.join()
+    }
+}
+
+/// Wait on the channel for a blocking machine state, while also watching
+/// for cancellation and timeout. Returns the exit reason if the machine
+/// should stop.
+fn wait(
+    state: MachineState,
+    input: &Receiver<isize>,
+    output: &Sender<isize>,
+    cancel: &mut Receiver<()>,
+    timer: &Receiver<Instant>,
+    next_input: &Cell<Option<isize>>,
+) -> Option<ExitReason> {
+    loop {
+        let cancelled = match state {
+            MachineState::Halt => return Some(ExitReason::Halted),
+            MachineState::NeedInput => select! {
+                recv(input) -> value => return match value {
+                    Ok(value) => {
+                        next_input.set(Some(value));
+                        None
+                    }
+                    Err(..) => Some(ExitReason::InputClosed),
+                },
+                recv(cancel) -> msg => msg,
+                recv(timer) -> _ => return Some(ExitReason::TimedOut),
+            },
+            MachineState::Output(value) => select! {
+                send(output, value) -> result => {
+                    return result.err().map(|_| ExitReason::OutputClosed)
+                },
+                recv(cancel) -> msg => msg,
+                recv(timer) -> _ => return Some(ExitReason::TimedOut),
+            },
+        };
+
+        match cancelled {
+            Ok(()) => return Some(ExitReason::Cancelled),
+            // The handle was dropped, so we can't be cancelled
+            Err(..) => *cancel = channel::never(),
+        }
+    }
+}
+
+/// Run a machine in this thread until it stops, receiving its inputs and
+/// sending its outputs through the channels.
+fn run_threaded<M: Memory<Word = isize>>(
+    machine: &mut Machine<M>,
+    input: Receiver<isize>,
+    output: Sender<isize>,
+    mut cancel: Receiver<()>,
+    timeout: Option<Duration>,
+) -> ExitReason {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let timer = timeout.map_or_else(channel::never, channel::after);
+
+    let next_input = Cell::new(None);
+    let mut stepper = try_step(iter::from_fn(|| next_input.take()));
+    let mut until_check = CHECK_INTERVAL;
+
+    loop {
+        until_check -= 1;
+
+        if until_check == 0 {
+            until_check = CHECK_INTERVAL;
+
+            match cancel.try_recv() {
+                Ok(()) => return ExitReason::Cancelled,
+                Err(channel::TryRecvError::Disconnected) => cancel = channel::never(),
+                Err(channel::TryRecvError::Empty) => {}
+            }
+
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return ExitReason::TimedOut;
+                }
+            }
+        }
+
+        match stepper(machine) {
+            Err(err) => return ExitReason::Fault(err),
+            Ok(None) => {}
+            Ok(Some(state)) => {
+                if let Some(reason) = wait(state, &input, &output, &mut cancel, &timer, &next_input)
+                {
+                    return reason;
+                }
+            }
+        }
+    }
+}
+
+fn spawn<M: Memory<Word = isize> + Send + 'static>(
+    mut machine: Machine<M>,
+    input: Receiver<isize>,
+    output: Sender<isize>,
+    timeout: Option<Duration>,
+) -> MachineHandle<M> {
+    let (send_cancel, recv_cancel) = channel::bounded(1);
+
+    let thread = thread::spawn(move || {
+        let reason = run_threaded(&mut machine, input, output, recv_cancel, timeout);
+        (machine, reason)
+    });
+
+    MachineHandle {
+        thread,
+        cancel: send_cancel,
+    }
+}
+
+/// Run a machine in a background thread. The machine will read inputs and
+/// send outputs to the channels, blocking if necessary. It will gracefully
+/// stop if either channel is closed. It will close the output channel if
+/// it halts.
+pub fn threaded_machine<M: Memory<Word = isize> + Send + 'static>(
+    machine: Machine<M>,
+    input: Receiver<isize>,
+    output: Sender<isize>,
+) -> MachineHandle<M> {
+    spawn(machine, input, output, None)
+}
+
+pub fn make_threaded_machine<M: Memory<Word = isize> + Send + 'static>(
+    machine: Machine<M>,
+) -> (Sender<isize>, Receiver<isize>) {
+    let (send_input, recv_input) = channel::unbounded();
+    let (send_output, recv_output) = channel::unbounded();
+
+    threaded_machine(machine, recv_input, send_output);
+
+    (send_input, recv_output)
+}
+
+/// Run a machine in a background thread, like `make_threaded_machine`, with
+/// the given channel capacities and timeout. Returns the input and output
+/// channels, and a handle for the thread.
+pub fn spawn_machine<M: Memory<Word = isize> + Send + 'static>(
+    machine: Machine<M>,
+    options: ThreadOptions,
+) -> (Sender<isize>, Receiver<isize>, MachineHandle<M>) {
+    let make_channel = |capacity: Option<usize>| match capacity {
+        Some(capacity) => channel::bounded(capacity),
+        None => channel::unbounded(),
+    };
+
+    let (send_input, recv_input) = make_channel(options.input_capacity);
+    let (send_output, recv_output) = make_channel(options.output_capacity);
+
+    let handle = spawn(machine, recv_input, send_output, options.timeout);
+
+    (send_input, recv_output, handle)
+}
@@ -1,8 +1,7 @@
-use super::Machine;
+use super::{Machine, Memory, Word};
 
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::fmt::{self, Debug, Display, Formatter};
-use std::mem;
 
 pub trait AddUsize: Sized + Copy {
     fn add_usize(self, rhs: usize) -> usize;
@@ -24,50 +23,66 @@ impl AddUsize for isize {
     }
 }
 
-/// A value which can be received or computed from a machine.
-pub trait Value: Sized {
+/// A value which can be used as an address
+pub trait ToAddress: Display {
+    fn to_address(&self) -> Option<usize>;
+}
+
+impl ToAddress for usize {
+    #[inline(always)]
+    fn to_address(&self) -> Option<usize> {
+        Some(*self)
+    }
+}
+
+impl<W: Word> ToAddress for W {
+    #[inline(always)]
+    fn to_address(&self) -> Option<usize> {
+        Word::to_address(self)
+    }
+}
+
+/// A value which can be received or computed from a machine whose memory
+/// holds `W` words.
+pub trait Value<W: Word = isize>: Sized {
     type Output;
 
     /// Get the value from the machine
-    fn get(&self, machine: &Machine) -> Self::Output;
+    fn get<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> Self::Output;
+}
+
+/// A value associated with an addressed location in the machine. Can be used
+/// as an Value, and can also be used as a destination for writes.
+pub trait Addressed<W: Word = isize>: Sized {
+    /// Get the address of this value
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize;
+}
 
+/// Methods for building values out of other values. These are separate from
+/// `Value` and `Addressed` because they don't depend on the word type; if
+/// they were methods of those traits, the word type of every intermediate
+/// value would need to be spelled out.
+pub trait Combinator: Sized {
     /// Turn the value into an address; create an `Addressed` which
     /// retreives the value at the address provided by this value
     #[inline(always)]
-    fn deref(self) -> Deref<Self>
-    where
-        Self::Output: TryInto<usize>,
-    {
+    fn deref(self) -> Deref<Self> {
         Deref { inner: self }
     }
 
     #[inline(always)]
-    fn map<R, F: Fn(Self::Output) -> R>(self, func: F) -> Unary<Self, F> {
+    fn map<F>(self, func: F) -> Unary<Self, F> {
         Unary { value: self, func }
     }
 
     #[inline(always)]
-    fn with_relative_base(self) -> RBOffset<Self>
-    where
-        Self: Value<Output = isize>,
-    {
+    fn with_relative_base(self) -> RBOffset<Self> {
         RBOffset { offset: self }
     }
-}
-
-/// A value associated with an addressed location in the machine. Can be used
-/// as an Value, and can also be used as a destination for writes.
-pub trait Addressed: Sized {
-    /// Get the address of this value
-    fn address(&self, machine: &Machine) -> usize;
 
-    /// Get the value at the offset location from this one
+    /// Get the value at the offset location from this addressed value
     #[inline(always)]
-    fn offset<T>(self, offset: T) -> Relative<Self, T>
-    where
-        T: Value,
-        T::Output: AddUsize,
-    {
+    fn offset<T>(self, offset: T) -> Relative<Self, T> {
         Relative {
             inner: self,
             offset,
@@ -75,87 +90,148 @@ pub trait Addressed: Sized {
     }
 }
 
-/// An addressed value returns the value in the machine at the given address
-impl<T: Addressed> Value for T {
-    type Output = isize;
+macro_rules! combinator {
+    ($($name:ident $(<$($param:ident),*>)?),*) => {$(
+        impl $(<$($param),*>)? Combinator for $name $(<$($param),*>)? {}
+    )*};
+}
 
-    #[inline(always)]
-    fn get(&self, machine: &Machine) -> isize {
-        let address = self.address(machine);
-        machine.memory.get(address).copied().unwrap_or(0)
-    }
+combinator! {
+    isize,
+    usize,
+    i32,
+    Literal<W>,
+    IP,
+    Parameter,
+    RBOffset<T>,
+    Relative<T, U>,
+    Deref<T>,
+    Unary<T, F>,
+    Binary<T, U, F>,
+    CondAddress<C, T, F>
+}
+
+/// An addressed value returns the value in the machine at the given address.
+/// This can't be a blanket impl over `Addressed`, since it would overlap with
+/// the literal impls below.
+macro_rules! addressed_value {
+    ($($name:ident $(<$($param:ident),*>)?),*) => {$(
+        impl<W: Word $($(, $param)*)?> Value<W> for $name $(<$($param),*>)?
+        where
+            Self: Addressed<W>,
+        {
+            type Output = W;
+
+            #[inline(always)]
+            fn get<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> W {
+                let address = self.address(machine);
+                machine.memory.load(address)
+            }
+        }
+    )*};
+}
+
+addressed_value! {
+    IP,
+    Parameter,
+    RBOffset<T>,
+    Relative<T, U>,
+    Deref<T>,
+    CondAddress<C, T, F>
 }
 
 /// A isize acts as a literal value; it always returns itself.
-impl Value for isize {
+impl<W: Word> Value<W> for isize {
     type Output = isize;
 
     #[inline(always)]
-    fn get(&self, _machine: &Machine) -> isize {
+    fn get<M: Memory<Word = W>>(&self, _machine: &Machine<M>) -> isize {
         *self
     }
 }
 
 /// A usize acts as a literal value; it always returns itself.
-impl Value for usize {
+impl<W: Word> Value<W> for usize {
     type Output = usize;
 
     #[inline(always)]
-    fn get(&self, _machine: &Machine) -> usize {
+    fn get<M: Memory<Word = W>>(&self, _machine: &Machine<M>) -> usize {
         *self
     }
 }
 
 /// We have i32 as well for cases when rustc defaults an integer literal to i32
-impl Value for i32 {
+impl<W: Word> Value<W> for i32 {
     type Output = isize;
 
     #[inline(always)]
-    fn get(&self, _machine: &Machine) -> isize {
+    fn get<M: Memory<Word = W>>(&self, _machine: &Machine<M>) -> isize {
         (*self) as isize
     }
 }
 
+/// A literal word, for machines whose words aren't `isize`
+#[derive(Debug, Clone)]
+pub struct Literal<W> {
+    value: W,
+}
+
+impl<W: Word> Value<W> for Literal<W> {
+    type Output = W;
+
+    #[inline(always)]
+    fn get<M: Memory<Word = W>>(&self, _machine: &Machine<M>) -> W {
+        self.value.clone()
+    }
+}
+
+#[inline(always)]
+pub const fn literal<W>(value: W) -> Literal<W> {
+    Literal { value }
+}
+
 #[derive(Debug, Clone)]
 pub struct IP;
 
-impl Addressed for IP {
+impl<W: Word> Addressed<W> for IP {
     #[inline(always)]
-    fn address(&self, machine: &Machine) -> usize {
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize {
         machine.instruction_pointer
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct RBOffset<T: Value<Output = isize>> {
+pub struct RBOffset<T> {
     offset: T,
 }
 
-impl<T: Value<Output = isize>> Addressed for RBOffset<T> {
+impl<W: Word, T: Value<W, Output = W>> Addressed<W> for RBOffset<T> {
     #[inline(always)]
-    fn address(&self, machine: &Machine) -> usize {
-        (machine.relative_base + self.offset.get(machine))
-            .try_into()
-            .unwrap()
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize {
+        let offset = self.offset.get(machine);
+
+        // Add in word space, so that an offset which doesn't fit in an isize
+        // can still give a valid address, as `check_instruction` allows
+        W::from_isize(machine.relative_base)
+            .checked_add(&offset)
+            .and_then(|address| address.to_address())
+            .unwrap_or_else(|| panic!("Invalid address: {} + {}", machine.relative_base, offset))
     }
 }
 
 /// A addressed value at a positive offset from another addressed value.
 #[derive(Debug, Clone)]
-pub struct Relative<T: Addressed, U: Value>
-where
-    U::Output: AddUsize,
-{
+pub struct Relative<T, U> {
     inner: T,
     offset: U,
 }
 
-impl<T: Addressed, U: Value> Addressed for Relative<T, U>
+impl<W: Word, T: Addressed<W>, U: Value<W>> Addressed<W> for Relative<T, U>
 where
     U::Output: AddUsize,
 {
     #[inline(always)]
-    fn address(&self, machine: &Machine) -> usize {
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize {
         let address = self.inner.address(machine);
         let offset = self.offset.get(machine);
 
@@ -165,24 +241,20 @@ where
 
 /// The value at the address of the inner value
 #[derive(Debug, Clone)]
-pub struct Deref<T: Value>
-where
-    T::Output: TryInto<usize>,
-{
+pub struct Deref<T> {
     inner: T,
 }
 
-impl<R, T> Addressed for Deref<T>
+impl<W: Word, T: Value<W>> Addressed<W> for Deref<T>
 where
-    R: TryInto<usize> + Display + Copy,
-    T: Value<Output = R>,
+    T::Output: ToAddress,
 {
     #[inline(always)]
-    fn address(&self, machine: &Machine) -> usize {
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize {
         let address = self.inner.get(machine);
         address
-            .try_into()
-            .unwrap_or_else(|_err| panic!("Invalid address: {}", address))
+            .to_address()
+            .unwrap_or_else(|| panic!("Invalid address: {}", address))
     }
 }
 
@@ -193,21 +265,21 @@ pub const fn address(value: usize) -> Deref<usize> {
 
 /// Apply a mapping function to the underlying value
 #[derive(Clone)]
-pub struct Unary<T: Value, F> {
+pub struct Unary<T, F> {
     value: T,
     func: F,
 }
 
-impl<O, T: Value, F: Fn(T::Output) -> O> Value for Unary<T, F> {
+impl<W: Word, O, T: Value<W>, F: Fn(T::Output) -> O> Value<W> for Unary<T, F> {
     type Output = O;
 
     #[inline(always)]
-    fn get(&self, machine: &Machine) -> O {
+    fn get<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> O {
         (self.func)(self.value.get(machine))
     }
 }
 
-impl<T: Value + Debug, F: Fn(isize) -> isize> Debug for Unary<T, F> {
+impl<T: Debug, F> Debug for Unary<T, F> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct("Unary")
             .field("value", &self.value)
@@ -218,22 +290,28 @@ impl<T: Value + Debug, F: Fn(isize) -> isize> Debug for Unary<T, F> {
 
 // Compute a new value from two inner value
 #[derive(Clone)]
-pub struct Binary<T: Value, U: Value, F> {
+pub struct Binary<T, U, F> {
     lhs: T,
     rhs: U,
     func: F,
 }
 
-impl<O, T: Value, U: Value, F: Fn(T::Output, U::Output) -> O> Value for Binary<T, U, F> {
+impl<W, O, T, U, F> Value<W> for Binary<T, U, F>
+where
+    W: Word,
+    T: Value<W>,
+    U: Value<W>,
+    F: Fn(T::Output, U::Output) -> O,
+{
     type Output = O;
 
     #[inline(always)]
-    fn get(&self, machine: &Machine) -> O {
+    fn get<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> O {
         (self.func)(self.lhs.get(machine), self.rhs.get(machine))
     }
 }
 
-impl<T: Value + Debug, U: Value + Debug, F> Debug for Binary<T, U, F> {
+impl<T: Debug, U: Debug, F> Debug for Binary<T, U, F> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct("Binary")
             .field("lhs", &self.lhs)
@@ -244,11 +322,7 @@ impl<T: Value + Debug, U: Value + Debug, F> Debug for Binary<T, U, F> {
 }
 
 #[inline(always)]
-pub fn binary<O, T: Value, U: Value, F: Fn(T::Output, U::Output) -> O>(
-    lhs: T,
-    rhs: U,
-    func: F,
-) -> Binary<T, U, F> {
+pub fn binary<T, U, F>(lhs: T, rhs: U, func: F) -> Binary<T, U, F> {
     Binary { lhs, rhs, func }
 }
 
@@ -258,6 +332,13 @@ pub const fn opcode(instruction: isize) -> isize {
     instruction % 100
 }
 
+/// Given an instruction, get the mode digit of the index'th parameter. Index
+/// 1 is the hundreds digit, index 2 is the thousands digit, and so on.
+#[inline]
+pub fn parameter_mode(instruction: isize, index: usize) -> isize {
+    (instruction / 10isize.pow((index as u32) + 1)) % 10
+}
+
 // Get the index'th parameter for this instruction, based on the current IP
 // location and parameter modes
 #[derive(Debug, Clone)]
@@ -265,21 +346,22 @@ pub struct Parameter {
     index: usize,
 }
 
-impl Addressed for Parameter {
+impl<W: Word> Addressed<W> for Parameter {
     #[inline]
-    fn address(&self, machine: &Machine) -> usize {
-        let opcode = machine.get(IP);
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize {
         let index = self.index;
 
-        // Index == 1 => opcode / 100; Index == 2 => opcode / 1000;
-        match (opcode / 10isize.pow((index as u32) + 1)) % 10 {
-            0 => IP.offset(index).deref().address(machine),
-            1 => IP.offset(index).address(machine),
-            2 => IP.offset(index).with_relative_base().address(machine),
+        match machine
+            .instruction()
+            .map(|opcode| parameter_mode(opcode, index))
+        {
+            Some(0) => IP.offset(index).deref().address(machine),
+            Some(1) => IP.offset(index).address(machine),
+            Some(2) => IP.offset(index).with_relative_base().address(machine),
             _ => panic!(
                 "Invalid opcode mode at address {}: {}",
                 IP.address(machine),
-                opcode
+                machine.get(IP)
             ),
         }
     }
@@ -290,24 +372,20 @@ pub fn param(index: usize) -> Parameter {
 }
 
 #[derive(Debug, Clone)]
-pub struct CondAddress<C, T, F>
-where
-    C: Value<Output = bool>,
-    T: Addressed,
-    F: Addressed,
-{
+pub struct CondAddress<C, T, F> {
     cond: C,
     if_true: T,
     if_false: F,
 }
 
-impl<C, T, F> Addressed for CondAddress<C, T, F>
+impl<W, C, T, F> Addressed<W> for CondAddress<C, T, F>
 where
-    C: Value<Output = bool>,
-    T: Addressed,
-    F: Addressed,
+    W: Word,
+    C: Value<W, Output = bool>,
+    T: Addressed<W>,
+    F: Addressed<W>,
 {
-    fn address(&self, machine: &Machine) -> usize {
+    fn address<M: Memory<Word = W>>(&self, machine: &Machine<M>) -> usize {
         if self.cond.get(machine) {
             self.if_true.address(machine)
         } else {
@@ -316,12 +394,7 @@ where
     }
 }
 
-pub fn cond_address<C, T, F>(cond: C, if_true: T, if_false: F) -> CondAddress<C, T, F>
-where
-    C: Value<Output = bool>,
-    T: Addressed,
-    F: Addressed,
-{
+pub fn cond_address<C, T, F>(cond: C, if_true: T, if_false: F) -> CondAddress<C, T, F> {
     CondAddress {
         cond,
         if_true,
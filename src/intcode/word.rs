@@ -0,0 +1,189 @@
+//! The integer types a machine can compute with. Most programs fit in an
+//! `isize`, which is the default, but some need more room; a machine can
+//! use any `Word`, such as `i128` or an arbitrary-precision `BigInt`:
+//!
+//! ```ignore
+//! let mut machine: BigMachine = Machine::parse_words(input)?;
+//! ```
+//!
+//! Addresses, the relative base, and the decoded opcode and parameter modes
+//! are always machine-sized; words which don't fit are invalid in those
+//! positions.
+//!
+//! What happens when `add` or `mul` overflows a fixed-size word is chosen
+//! per machine with an `OverflowPolicy`, so that it doesn't depend on the
+//! build profile.
+//!
+//! Observers, and the debugger, profiler, and coverage built on them, work
+//! with any word. The decode cache and `AsciiMachine` only run `isize`
+//! machines.
+
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::ops::{Add, Mul};
+
+use num::{BigInt, ToPrimitive};
+
+/// An integer type which can be stored in a machine's memory
+pub trait Word:
+    Clone + Debug + Display + Default + Eq + Ord + Hash + Add<Output = Self> + Mul<Output = Self>
+{
+    fn from_isize(value: isize) -> Self;
+
+    /// Convert the word to an `isize`, if it fits
+    fn to_isize(&self) -> Option<isize>;
+
+    /// Convert the word to an address, if it's a valid one
+    fn to_address(&self) -> Option<usize>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn saturating_add(&self, other: &Self) -> Self;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+    fn saturating_mul(&self, other: &Self) -> Self;
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    #[inline]
+    fn is_negative(&self) -> bool {
+        *self < Self::default()
+    }
+}
+
+macro_rules! primitive_word {
+    ($($word:ty)*) => {$(
+        impl Word for $word {
+            #[inline(always)]
+            fn from_isize(value: isize) -> Self {
+                value as $word
+            }
+
+            #[inline(always)]
+            fn to_isize(&self) -> Option<isize> {
+                isize::try_from(*self).ok()
+            }
+
+            #[inline(always)]
+            fn to_address(&self) -> Option<usize> {
+                usize::try_from(*self).ok()
+            }
+
+            #[inline(always)]
+            fn checked_add(&self, other: &Self) -> Option<Self> {
+                <$word>::checked_add(*self, *other)
+            }
+
+            #[inline(always)]
+            fn wrapping_add(&self, other: &Self) -> Self {
+                <$word>::wrapping_add(*self, *other)
+            }
+
+            #[inline(always)]
+            fn saturating_add(&self, other: &Self) -> Self {
+                <$word>::saturating_add(*self, *other)
+            }
+
+            #[inline(always)]
+            fn checked_mul(&self, other: &Self) -> Option<Self> {
+                <$word>::checked_mul(*self, *other)
+            }
+
+            #[inline(always)]
+            fn wrapping_mul(&self, other: &Self) -> Self {
+                <$word>::wrapping_mul(*self, *other)
+            }
+
+            #[inline(always)]
+            fn saturating_mul(&self, other: &Self) -> Self {
+                <$word>::saturating_mul(*self, *other)
+            }
+        }
+    )*};
+}
+
+primitive_word! { isize i64 i128 }
+
+impl Word for BigInt {
+    fn from_isize(value: isize) -> Self {
+        value.into()
+    }
+
+    fn to_isize(&self) -> Option<isize> {
+        ToPrimitive::to_isize(self)
+    }
+
+    fn to_address(&self) -> Option<usize> {
+        ToPrimitive::to_usize(self)
+    }
+
+    // A BigInt never overflows, so every policy gives the same result
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn saturating_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn saturating_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+/// What a machine does when an arithmetic instruction overflows its word
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverflowPolicy {
+    /// Wrap around, as two's complement. This is the default.
+    #[default]
+    Wrapping,
+
+    /// Stop with an `IntcodeError::Overflow` from `try_step`, or panic from
+    /// `step`. The instruction isn't run.
+    Checked,
+
+    /// Clamp the result to the largest or smallest word
+    Saturating,
+}
+
+impl OverflowPolicy {
+    /// Add two words. Returns None if the sum overflows under the `Checked`
+    /// policy.
+    #[inline(always)]
+    pub fn add<W: Word>(self, a: &W, b: &W) -> Option<W> {
+        match self {
+            OverflowPolicy::Wrapping => Some(a.wrapping_add(b)),
+            OverflowPolicy::Checked => a.checked_add(b),
+            OverflowPolicy::Saturating => Some(a.saturating_add(b)),
+        }
+    }
+
+    /// Multiply two words. Returns None if the product overflows under the
+    /// `Checked` policy.
+    #[inline(always)]
+    pub fn mul<W: Word>(self, a: &W, b: &W) -> Option<W> {
+        match self {
+            OverflowPolicy::Wrapping => Some(a.wrapping_mul(b)),
+            OverflowPolicy::Checked => a.checked_mul(b),
+            OverflowPolicy::Saturating => Some(a.saturating_mul(b)),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+#![allow(unused_imports)]
+
+// Compares the closure interpreter (`run_until_block`) against the decoded
+// instruction cache (`DecodeCache`). Reads a program from stdin and runs it
+// from scratch repeatedly with each interpreter, using the same input each
+// time:
+//
+//     ./start.sh intcode_bench && cargo run --release -- [iterations] [inputs...] < program.txt
+//
+// For example, day 19 runs its program once per location checked:
+//
+//     cargo run --release -- 10000 20 30 < day19.txt
+
+mod intcode;
+use intcode::*;
+
+/// Run a machine until it halts or blocks on input, collecting its output
+fn collect_output(
+    mut run: impl FnMut(&mut Machine) -> MachineState,
+    machine: &mut Machine,
+) -> Vec<isize> {
+    let mut output = Vec::new();
+    while let MachineState::Output(value) = run(machine) {
+        output.push(value);
+    }
+    output
+}
+
+fn bench(
+    name: &str,
+    iterations: usize,
+    mut run_once: impl FnMut() -> Vec<isize>,
+) -> (Vec<isize>, Duration) {
+    let (output, duration) = timed(|| {
+        let mut output = Vec::new();
+        for _ in 0..iterations {
+            output = run_once();
+        }
+        output
+    });
+
+    println!(
+        "{:>8}: {:?} total, {:?} per run",
+        name,
+        duration,
+        duration.div_f64(iterations as f64)
+    );
+
+    (output, duration)
+}
+
+fn solve(input: &str, iterations: usize, program_input: &[isize]) -> impl Display {
+    let init = Machine::from_csv(input);
+    let mut machine = Machine::default();
+
+    let (closure_output, closure_time) = bench("closure", iterations, || {
+        machine.clone_from(&init);
+        collect_output(run_until_block(program_input.iter().copied()), &mut machine)
+    });
+
+    let mut cache = DecodeCache::new();
+    let (cached_output, cached_time) = bench("cached", iterations, || {
+        machine.clone_from(&init);
+        collect_output(
+            cache.run_until_block(program_input.iter().copied()),
+            &mut machine,
+        )
+    });
+
+    assert_eq!(
+        closure_output, cached_output,
+        "Interpreters produced different output"
+    );
+
+    lazy_format!(
+        "output: {}\nspeedup: {:.2}x",
+        cached_output.iter().join_with(", "),
+        closure_time.as_secs_f64() / cached_time.as_secs_f64(),
+    )
+}
+
+/*
+ * SUPPORTING LIBRARY CODE GOES HERE:
+ *
+ * - Imports & use statements for tons of common traits, data structures, etc
+ * - `fn main` bootstrap that reads from stdin and writes the results to stdout
+ */
+
+#[global_allocator]
+static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+use std::env;
+use std::fmt::Display;
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+// String joins
+use joinery::prelude::*;
+
+// Formatting things without creating intermediary strings
+use lazy_format::lazy_format;
+
+#[inline(always)]
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    let end = Instant::now();
+    (result, end - start)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+
+    let iterations = args.next().map_or(1000, |arg| match arg.parse() {
+        Ok(0) => panic!("Invalid iteration count {:?}: must be at least 1", arg),
+        Ok(iterations) => iterations,
+        Err(err) => panic!("Invalid iteration count {:?}: {}", arg, err),
+    });
+
+    let program_input: Vec<isize> = args
+        .map(|arg| {
+            arg.parse()
+                .unwrap_or_else(|err| panic!("Invalid input value {:?}: {}", arg, err))
+        })
+        .collect();
+
+    let mut input = String::with_capacity(4096);
+    io::stdin()
+        .read_to_string(&mut input)
+        .unwrap_or_else(|err| panic!("Error reading input from stdin: {}", err));
+
+    println!("{}", solve(&input, iterations, &program_input));
+}
@@ -0,0 +1,168 @@
+#![allow(unused_imports)]
+
+// An intcode profiler. Run with the path to a program and its input values:
+//
+//     ./start.sh profile && cargo run --release -- program.txt 1
+//
+// Prints the program's output, followed by a report of the instructions it
+// ran per opcode and the hottest addresses and memory cells.
+//
+// Options:
+//
+//     --top <n>            Show the n hottest addresses and cells (default 20)
+//     --csv <file>         Also write the per-address profile as CSV
+//     --opcode-csv <file>  Also write the per-opcode counts as CSV
+//     --restart            Restart the program from scratch whenever it
+//                          halts with input left over, like day 19's probe
+//     --coverage           Also print which regions of the program are code,
+//                          tables, and scratch space, and a listing annotated
+//                          with how each cell was used
+
+mod intcode;
+use intcode::*;
+
+struct Options {
+    path: String,
+    top: usize,
+    csv: Option<String>,
+    opcode_csv: Option<String>,
+    restart: bool,
+    coverage: bool,
+    input: Vec<isize>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut path = None;
+    let mut options = Options {
+        path: String::new(),
+        top: 20,
+        csv: None,
+        opcode_csv: None,
+        restart: false,
+        coverage: false,
+        input: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} needs an argument", name))
+        };
+
+        match arg.as_str() {
+            "--top" => {
+                options.top = value("--top")?
+                    .parse()
+                    .map_err(|err| format!("Invalid --top: {}", err))?
+            }
+            "--csv" => options.csv = Some(value("--csv")?),
+            "--opcode-csv" => options.opcode_csv = Some(value("--opcode-csv")?),
+            "--restart" => options.restart = true,
+            "--coverage" => options.coverage = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => options.input.push(
+                arg.parse()
+                    .map_err(|err| format!("Invalid input {:?}: {}", arg, err))?,
+            ),
+        }
+    }
+
+    options.path = path.ok_or_else(|| "Missing program path".to_string())?;
+    Ok(options)
+}
+
+fn profile(
+    program: &Machine,
+    options: &Options,
+    mut observer: impl Observer,
+) -> Result<MachineState, IntcodeError> {
+    let mut input = options.input.iter().copied().peekable();
+
+    loop {
+        let mut machine = program.clone();
+        let state = {
+            let mut runner = run_observed(input.by_ref(), &mut observer);
+
+            loop {
+                match runner(&mut machine)? {
+                    MachineState::Output(value) => println!("output: {}", value),
+                    state => break state,
+                }
+            }
+        };
+
+        match state {
+            MachineState::Halt if options.restart && input.peek().is_some() => {}
+            state => break Ok(state),
+        }
+    }
+}
+
+/*
+ * SUPPORTING LIBRARY CODE GOES HERE:
+ *
+ * - Imports & use statements for tons of common traits, data structures, etc
+ * - `fn main` bootstrap that loads the program and prints the profile
+ */
+
+#[global_allocator]
+static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::process;
+
+fn write_csv(path: &str, write: impl FnOnce(BufWriter<File>) -> io::Result<()>) {
+    File::create(path)
+        .and_then(|file| write(BufWriter::new(file)))
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to write {}: {}", path, err);
+            process::exit(1);
+        })
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!(
+            "Usage: profile <program.txt> [--top n] [--csv file] [--opcode-csv file] \
+             [--restart] [--coverage] [inputs...]"
+        );
+        process::exit(2);
+    });
+
+    let program = Machine::load_csv(&options.path).unwrap_or_else(|err| {
+        eprintln!("Failed to load {}: {}", options.path, err);
+        process::exit(1);
+    });
+
+    let mut profiler = Profiler::new();
+    let mut coverage = Coverage::new();
+
+    match profile(&program, &options, (&mut profiler, &mut coverage)) {
+        Ok(MachineState::Halt) => println!("halted"),
+        Ok(MachineState::NeedInput) => println!("waiting for input"),
+        Ok(state) => println!("stopped: {:?}", state),
+        Err(err) => println!("error: {}", err),
+    }
+
+    println!("\n{}", profiler.report(options.top));
+
+    if options.coverage {
+        println!();
+        for region in coverage.regions(&program) {
+            println!("{}", region);
+        }
+
+        print!("\n{}", coverage.annotate(&program.disassemble()));
+    }
+
+    if let Some(ref path) = options.csv {
+        write_csv(path, |dest| profiler.write_csv(dest));
+    }
+
+    if let Some(ref path) = options.opcode_csv {
+        write_csv(path, |dest| profiler.write_opcode_csv(dest));
+    }
+}